@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::path::PathBuf;
+
+use image::RgbaImage;
+use rustc_hash::FxHasher;
+
+use crate::game::AtlassedTexture;
+use crate::settings::Settings;
+
+//bump whenever the packing itself changes, so old caches arent picked up
+const CACHE_VERSION: u32 = 2;
+const CACHE_DIRECTORY: &str = "cache";
+
+pub fn cache_key(textures: &[(String, Vec<u8>)], settings: &Settings) -> u64 {
+    let mut sorted: Vec<_> = textures.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut hasher = FxHasher::default();
+    hasher.write_u32(CACHE_VERSION);
+    hasher.write_u32(settings.atlas_border_padding);
+    hasher.write_u32(settings.atlas_texture_padding);
+    hasher.write_u32(settings.atlas_texture_extrusion);
+    for (name, data) in sorted {
+        hasher.write(name.as_bytes());
+        hasher.write_usize(data.len());
+        hasher.write(data.as_slice());
+    }
+    hasher.finish()
+}
+
+fn cache_paths(key: u64) -> (PathBuf, PathBuf) {
+    let directory = PathBuf::from(CACHE_DIRECTORY);
+    (
+        directory.join(format!("atlas_{:016x}.png", key)),
+        directory.join(format!("atlas_{:016x}.json", key)),
+    )
+}
+
+pub fn load(key: u64) -> Option<(HashMap<String, AtlassedTexture>, RgbaImage)> {
+    let (image_path, frames_path) = cache_paths(key);
+    let frames = json::parse(std::fs::read_to_string(frames_path).ok()?.as_str()).ok()?;
+    let image = image::open(image_path).ok()?.to_rgba8();
+    let (atlas_w, atlas_h) = (frames["width"].as_u32()?, frames["height"].as_u32()?);
+    if image.width() != atlas_w || image.height() != atlas_h {
+        return None;
+    }
+    let mut textures = HashMap::new();
+    for (name, frame) in frames["frames"].entries() {
+        textures.insert(
+            name.to_string(),
+            AtlassedTexture {
+                x: frame[0].as_u32()?,
+                y: frame[1].as_u32()?,
+                w: frame[2].as_u32()?,
+                h: frame[3].as_u32()?,
+                atlas_w,
+                atlas_h,
+                animation: None,
+            },
+        );
+    }
+    Some((textures, image))
+}
+
+pub fn save(key: u64, textures: &HashMap<String, AtlassedTexture>, image: &RgbaImage) {
+    let (image_path, frames_path) = cache_paths(key);
+    let mut frames = json::object! {
+        width: image.width(),
+        height: image.height(),
+        frames: json::object!{},
+    };
+    for (name, texture) in textures {
+        frames["frames"][name.as_str()] = json::array![texture.x, texture.y, texture.w, texture.h];
+    }
+    let result = std::fs::create_dir_all(CACHE_DIRECTORY)
+        .map_err(|error| error.to_string())
+        .and_then(|_| image.save(image_path).map_err(|error| error.to_string()))
+        .and_then(|_| {
+            std::fs::write(frames_path, frames.dump()).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        println!("couldnt write texture atlas cache: {}", error);
+    }
+}
@@ -0,0 +1,287 @@
+//plain representation of the big endian .bbm format, model::Model is built from it
+//and bbmodel_to_bbm writes it
+use endio::BERead;
+use endio::BEWrite;
+
+//files start with the magic and a version, bump it whenever the layout below changes
+pub const MAGIC: [u8; 4] = *b"BBM\0";
+pub const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum ModelError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidString,
+}
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::BadMagic => write!(f, "not a bbm model or saved without a header"),
+            ModelError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported bbm version {}, expected {}",
+                version, VERSION
+            ),
+            ModelError::Truncated => write!(f, "model data ends unexpectedly"),
+            ModelError::InvalidString => write!(f, "model contains a name that isnt utf-8"),
+        }
+    }
+}
+impl From<std::io::Error> for ModelError {
+    fn from(_: std::io::Error) -> Self {
+        ModelError::Truncated
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BbmModel {
+    pub root_bone: BbmBone,
+    pub animations: Vec<BbmAnimation>,
+}
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BbmBone {
+    pub name: String,
+    pub origin: [f32; 3],
+    pub child_bones: Vec<BbmBone>,
+    pub cube_elements: Vec<BbmCube>,
+    pub item_elements: Vec<BbmItem>,
+    //keyed by index into BbmModel::animations
+    pub animations: Vec<(u32, BbmAnimationData)>,
+}
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BbmCube {
+    pub position: [f32; 3],
+    pub scale: [f32; 3],
+    pub rotation: [f32; 3],
+    pub origin: [f32; 3],
+    pub front: BbmFace,
+    pub back: BbmFace,
+    pub left: BbmFace,
+    pub right: BbmFace,
+    pub up: BbmFace,
+    pub down: BbmFace,
+}
+//uvs are normalized to the model texture
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct BbmFace {
+    pub u1: f32,
+    pub v1: f32,
+    pub u2: f32,
+    pub v2: f32,
+}
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BbmItem {
+    pub name: String,
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub origin: [f32; 3],
+    pub size: [f32; 2],
+}
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BbmAnimation {
+    pub name: String,
+    pub length: f32,
+}
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BbmAnimationData {
+    pub position: Vec<BbmKeyframe>,
+    pub rotation: Vec<BbmKeyframe>,
+    pub scale: Vec<BbmKeyframe>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct BbmKeyframe {
+    pub data: [f32; 3],
+    pub time: f32,
+}
+
+impl BbmModel {
+    pub fn to_data(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.write_be(VERSION).unwrap();
+        self.root_bone.write(&mut data);
+        data.write_be(self.animations.len() as u32).unwrap();
+        for animation in &self.animations {
+            write_string(&mut data, &animation.name);
+            data.write_be(animation.length).unwrap();
+        }
+        data
+    }
+    pub fn from_data(data: &[u8]) -> Result<Self, ModelError> {
+        let data = data.strip_prefix(&MAGIC).ok_or(ModelError::BadMagic)?;
+        let (version, data) = data.split_first_chunk::<2>().ok_or(ModelError::Truncated)?;
+        let version = u16::from_be_bytes(*version);
+        if version != VERSION {
+            return Err(ModelError::UnsupportedVersion(version));
+        }
+        BbmModel::from_legacy_data(data)
+    }
+    //models written before the header was added
+    pub fn from_legacy_data(mut data: &[u8]) -> Result<Self, ModelError> {
+        let data = &mut data;
+        let root_bone = BbmBone::read(data)?;
+        //counts come from the file, so nothing is preallocated from them
+        let animations_cnt: u32 = data.read_be()?;
+        let mut animations = Vec::new();
+        for _ in 0..animations_cnt {
+            animations.push(BbmAnimation {
+                name: read_string(data)?,
+                length: data.read_be()?,
+            });
+        }
+        Ok(BbmModel {
+            root_bone,
+            animations,
+        })
+    }
+}
+impl BbmBone {
+    fn write(&self, data: &mut Vec<u8>) {
+        write_string(data, &self.name);
+        write_floats(data, &self.origin);
+        data.write_be(self.child_bones.len() as u32).unwrap();
+        for child in &self.child_bones {
+            child.write(data);
+        }
+        data.write_be(self.cube_elements.len() as u32).unwrap();
+        for cube in &self.cube_elements {
+            write_floats(data, &cube.position);
+            write_floats(data, &cube.scale);
+            write_floats(data, &cube.rotation);
+            write_floats(data, &cube.origin);
+            for face in [
+                &cube.front,
+                &cube.back,
+                &cube.left,
+                &cube.right,
+                &cube.up,
+                &cube.down,
+            ] {
+                write_floats(data, &[face.u1, face.v1, face.u2, face.v2]);
+            }
+        }
+        data.write_be(self.item_elements.len() as u32).unwrap();
+        for item in &self.item_elements {
+            write_string(data, &item.name);
+            write_floats(data, &item.position);
+            write_floats(data, &item.rotation);
+            write_floats(data, &item.origin);
+            write_floats(data, &item.size);
+        }
+        data.write_be(self.animations.len() as u32).unwrap();
+        for (id, animation) in &self.animations {
+            data.write_be(*id).unwrap();
+            for keyframes in [&animation.position, &animation.rotation, &animation.scale] {
+                data.write_be(keyframes.len() as u32).unwrap();
+                for keyframe in keyframes {
+                    write_floats(data, &keyframe.data);
+                    data.write_be(keyframe.time).unwrap();
+                }
+            }
+        }
+    }
+    fn read(data: &mut &[u8]) -> Result<Self, ModelError> {
+        let name = read_string(data)?;
+        let origin = read_floats(data)?;
+        let child_bones_cnt: u32 = data.read_be()?;
+        let mut child_bones = Vec::new();
+        for _ in 0..child_bones_cnt {
+            child_bones.push(BbmBone::read(data)?);
+        }
+        let cube_elements_cnt: u32 = data.read_be()?;
+        let mut cube_elements = Vec::new();
+        for _ in 0..cube_elements_cnt {
+            let position = read_floats(data)?;
+            let scale = read_floats(data)?;
+            let rotation = read_floats(data)?;
+            let origin = read_floats(data)?;
+            let mut faces = [BbmFace::default(); 6];
+            for face in &mut faces {
+                let [u1, v1, u2, v2] = read_floats(data)?;
+                *face = BbmFace { u1, v1, u2, v2 };
+            }
+            let [front, back, left, right, up, down] = faces;
+            cube_elements.push(BbmCube {
+                position,
+                scale,
+                rotation,
+                origin,
+                front,
+                back,
+                left,
+                right,
+                up,
+                down,
+            });
+        }
+        let item_elements_cnt: u32 = data.read_be()?;
+        let mut item_elements = Vec::new();
+        for _ in 0..item_elements_cnt {
+            item_elements.push(BbmItem {
+                name: read_string(data)?,
+                position: read_floats(data)?,
+                rotation: read_floats(data)?,
+                origin: read_floats(data)?,
+                size: read_floats(data)?,
+            });
+        }
+        let animations_cnt: u32 = data.read_be()?;
+        let mut animations = Vec::new();
+        for _ in 0..animations_cnt {
+            let id = data.read_be()?;
+            let mut channels = [Vec::new(), Vec::new(), Vec::new()];
+            for keyframes in &mut channels {
+                let keyframes_cnt: u32 = data.read_be()?;
+                for _ in 0..keyframes_cnt {
+                    keyframes.push(BbmKeyframe {
+                        data: read_floats(data)?,
+                        time: data.read_be()?,
+                    });
+                }
+            }
+            let [position, rotation, scale] = channels;
+            animations.push((
+                id,
+                BbmAnimationData {
+                    position,
+                    rotation,
+                    scale,
+                },
+            ));
+        }
+        Ok(BbmBone {
+            name,
+            origin,
+            child_bones,
+            cube_elements,
+            item_elements,
+            animations,
+        })
+    }
+}
+
+fn write_string(data: &mut Vec<u8>, value: &str) {
+    data.write_be(value.len() as u16).unwrap();
+    data.extend_from_slice(value.as_bytes());
+}
+fn read_string(data: &mut &[u8]) -> Result<String, ModelError> {
+    let len: u16 = data.read_be()?;
+    if data.len() < len as usize {
+        return Err(ModelError::Truncated);
+    }
+    let (string, rest) = data.split_at(len as usize);
+    *data = rest;
+    String::from_utf8(string.to_vec()).map_err(|_| ModelError::InvalidString)
+}
+fn write_floats<const N: usize>(data: &mut Vec<u8>, values: &[f32; N]) {
+    for value in values {
+        data.write_be(*value).unwrap();
+    }
+}
+fn read_floats<const N: usize>(data: &mut &[u8]) -> Result<[f32; N], ModelError> {
+    let mut values = [0.; N];
+    for value in &mut values {
+        *value = data.read_be()?;
+    }
+    Ok(values)
+}
@@ -0,0 +1,286 @@
+//converts blockbench .bbmodel files into the .bbm models the client loads
+//usage: bbmodel_to_bbm <input.bbmodel> [output.bbm]
+//       bbmodel_to_bbm --check <model.bbm>...
+//       bbmodel_to_bbm --upgrade <model.bbm>...
+//every written model is read back and compared, --check does the same for existing files
+//
+//groups become bones, cubes keep their uvs relative to the project resolution,
+//locators and cubes named item_<name> become item elements, bone animators become keyframes
+#[path = "../bbm.rs"]
+mod bbm;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use bbm::*;
+use json::JsonValue;
+
+const PIXELS_PER_BLOCK: f32 = 16.;
+//locators have no size, items attached to them are drawn one block big
+const LOCATOR_ITEM_SIZE: [f32; 2] = [1., 1.];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(|arg| arg.as_str()) {
+        Some("--check") if args.len() > 1 => args[1..].iter().try_for_each(|path| check(path)),
+        Some("--upgrade") if args.len() > 1 => {
+            args[1..].iter().try_for_each(|path| upgrade(path))
+        }
+        Some(input) if args.len() <= 2 => convert(
+            input,
+            args.get(1).map(PathBuf::from).unwrap_or_else(|| {
+                let mut output = PathBuf::from(input);
+                output.set_extension("bbm");
+                output
+            }),
+        ),
+        _ => Err(
+            "usage: bbmodel_to_bbm <input.bbmodel> [output.bbm] | --check <model.bbm>... | --upgrade <model.bbm>..."
+                .to_string(),
+        ),
+    };
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn convert(input: &str, output: PathBuf) -> Result<(), String> {
+    let json = std::fs::read_to_string(input)
+        .map_err(|error| format!("couldnt read {}: {}", input, error))
+        .and_then(|text| {
+            json::parse(text.as_str()).map_err(|error| format!("invalid {}: {}", input, error))
+        })?;
+    let model = Converter::new(&json).convert();
+    let data = model.to_data();
+    verify(&data, Some(&model))?;
+    std::fs::write(&output, data)
+        .map_err(|error| format!("couldnt write {}: {}", output.display(), error))?;
+    println!("{} -> {}", input, output.display());
+    Ok(())
+}
+
+fn check(path: &str) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|error| format!("couldnt read {}: {}", path, error))?;
+    verify(&data, None).map_err(|error| format!("{}: {}", path, error))?;
+    println!("{} ok", path);
+    Ok(())
+}
+
+//adds the header to models written before it existed
+fn upgrade(path: &str) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|error| format!("couldnt read {}: {}", path, error))?;
+    if BbmModel::from_data(data.as_slice()).is_ok() {
+        println!("{} is up to date", path);
+        return Ok(());
+    }
+    let model = BbmModel::from_legacy_data(data.as_slice()).map_err(|error| {
+        format!(
+            "{} is neither a current nor a legacy model: {}",
+            path, error
+        )
+    })?;
+    let data = model.to_data();
+    verify(&data, Some(&model)).map_err(|error| format!("{}: {}", path, error))?;
+    std::fs::write(path, data).map_err(|error| format!("couldnt write {}: {}", path, error))?;
+    println!("{} upgraded", path);
+    Ok(())
+}
+
+//the data has to parse back into the same model and encode to the same bytes
+fn verify(data: &[u8], expected: Option<&BbmModel>) -> Result<(), String> {
+    let parsed =
+        BbmModel::from_data(data).map_err(|error| format!("model doesnt parse back: {}", error))?;
+    if let Some(expected) = expected {
+        if &parsed != expected {
+            return Err("parsed model differs from the converted one".to_string());
+        }
+    }
+    if &parsed.to_data() != data {
+        return Err("model doesnt encode back to the same data".to_string());
+    }
+    Ok(())
+}
+
+struct Converter<'a> {
+    json: &'a JsonValue,
+    elements: HashMap<&'a str, &'a JsonValue>,
+    resolution: (f32, f32),
+    //java block models go from 0 to 16, the client expects models centered on x and z
+    offset: [f32; 3],
+}
+impl<'a> Converter<'a> {
+    fn new(json: &'a JsonValue) -> Self {
+        let mut elements = HashMap::new();
+        for element in json["elements"].members() {
+            if let Some(uuid) = element["uuid"].as_str() {
+                elements.insert(uuid, element);
+            }
+        }
+        Converter {
+            json,
+            elements,
+            resolution: (
+                json["resolution"]["width"].as_f32().unwrap_or(16.),
+                json["resolution"]["height"].as_f32().unwrap_or(16.),
+            ),
+            offset: if json["meta"]["model_format"].as_str() == Some("java_block") {
+                [-8., 0., -8.]
+            } else {
+                [0., 0., 0.]
+            },
+        }
+    }
+    fn convert(&self) -> BbmModel {
+        let root = json::object! {
+            name: "root",
+            origin: [0, 0, 0],
+            children: self.json["outliner"].clone(),
+        };
+        let mut root_bone = self.convert_bone(&root);
+        root_bone.origin = [0., 0., 0.];
+        let animations = self.json["animations"]
+            .members()
+            .map(|animation| BbmAnimation {
+                name: animation["name"].as_str().unwrap_or("").to_string(),
+                length: animation["length"].as_f32().unwrap_or(0.),
+            })
+            .collect();
+        BbmModel {
+            root_bone,
+            animations,
+        }
+    }
+    fn convert_bone(&self, group: &JsonValue) -> BbmBone {
+        let mut bone = BbmBone {
+            name: group["name"].as_str().unwrap_or("").to_string(),
+            origin: self.position(&group["origin"]),
+            ..Default::default()
+        };
+        for child in group["children"].members() {
+            if child.is_object() {
+                bone.child_bones.push(self.convert_bone(child));
+                continue;
+            }
+            let Some(element) = child.as_str().and_then(|uuid| self.elements.get(uuid)) else {
+                continue;
+            };
+            let name = element["name"].as_str().unwrap_or("");
+            match element["type"].as_str().unwrap_or("cube") {
+                "cube" if name.starts_with("item_") => {
+                    let (from, to) = self.cube_bounds(element);
+                    bone.item_elements.push(BbmItem {
+                        name: name["item_".len()..].to_string(),
+                        position: from,
+                        rotation: radians(&element["rotation"]),
+                        origin: self.position(&element["origin"]),
+                        size: [to[0] - from[0], to[1] - from[1]],
+                    });
+                }
+                "cube" => bone.cube_elements.push(self.convert_cube(element)),
+                "locator" => bone.item_elements.push(BbmItem {
+                    name: name.to_string(),
+                    position: self.position(&element["position"]),
+                    rotation: radians(&element["rotation"]),
+                    origin: self.position(&element["position"]),
+                    size: LOCATOR_ITEM_SIZE,
+                }),
+                other => println!("skipping {} element {}", other, name),
+            }
+        }
+        if let Some(uuid) = group["uuid"].as_str() {
+            for (id, animation) in self.json["animations"].members().enumerate() {
+                let animator = &animation["animators"][uuid];
+                if !animator.is_null() {
+                    bone.animations
+                        .push((id as u32, convert_animator(animator)));
+                }
+            }
+        }
+        bone
+    }
+    fn convert_cube(&self, element: &JsonValue) -> BbmCube {
+        let (from, to) = self.cube_bounds(element);
+        let face = |name: &str| {
+            let uv = &element["faces"][name]["uv"];
+            BbmFace {
+                u1: number(&uv[0]) / self.resolution.0,
+                v1: number(&uv[1]) / self.resolution.1,
+                u2: number(&uv[2]) / self.resolution.0,
+                v2: number(&uv[3]) / self.resolution.1,
+            }
+        };
+        BbmCube {
+            position: from,
+            scale: [to[0] - from[0], to[1] - from[1], to[2] - from[2]],
+            rotation: radians(&element["rotation"]),
+            origin: self.position(&element["origin"]),
+            front: face("north"),
+            back: face("south"),
+            left: face("west"),
+            right: face("east"),
+            up: face("up"),
+            down: face("down"),
+        }
+    }
+    fn cube_bounds(&self, element: &JsonValue) -> ([f32; 3], [f32; 3]) {
+        let inflate = element["inflate"].as_f32().unwrap_or(0.) / PIXELS_PER_BLOCK;
+        let from = self.position(&element["from"]).map(|value| value - inflate);
+        let to = self.position(&element["to"]).map(|value| value + inflate);
+        (from, to)
+    }
+    fn position(&self, json: &JsonValue) -> [f32; 3] {
+        [0, 1, 2].map(|i| (number(&json[i]) + self.offset[i]) / PIXELS_PER_BLOCK)
+    }
+}
+
+fn convert_animator(animator: &JsonValue) -> BbmAnimationData {
+    let mut animation = BbmAnimationData::default();
+    for keyframe in animator["keyframes"].members() {
+        let point = &keyframe["data_points"][0];
+        let value = [&point["x"], &point["y"], &point["z"]].map(number);
+        let (keyframes, data) = match keyframe["channel"].as_str() {
+            Some("position") => (
+                &mut animation.position,
+                value.map(|value| value / PIXELS_PER_BLOCK),
+            ),
+            Some("rotation") => (&mut animation.rotation, value.map(f32::to_radians)),
+            Some("scale") => (&mut animation.scale, value),
+            _ => continue,
+        };
+        keyframes.push(BbmKeyframe {
+            data,
+            time: keyframe["time"].as_f32().unwrap_or(0.),
+        });
+    }
+    for keyframes in [
+        &mut animation.position,
+        &mut animation.rotation,
+        &mut animation.scale,
+    ] {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+    animation
+}
+
+fn radians(json: &JsonValue) -> [f32; 3] {
+    [0, 1, 2].map(|i| number(&json[i]).to_radians())
+}
+//keyframe values are strings in blockbench, molang expressions cant be converted
+fn number(json: &JsonValue) -> f32 {
+    if let Some(value) = json.as_f32() {
+        return value;
+    }
+    let text = json.as_str().unwrap_or("").trim();
+    if text.is_empty() {
+        return 0.;
+    }
+    text.parse().unwrap_or_else(|_| {
+        println!("unsupported keyframe expression {}, using 0", text);
+        0.
+    })
+}
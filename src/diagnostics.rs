@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::panic::Location;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Texture,
+    Model,
+}
+impl Display for AssetKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetKind::Texture => write!(f, "texture"),
+            AssetKind::Model => write!(f, "model"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingAsset {
+    pub kind: AssetKind,
+    pub name: String,
+    pub reason: String,
+    //block/item/entity/gui element that asked for the asset
+    pub requester: String,
+    pub location: &'static Location<'static>,
+}
+impl Display for MissingAsset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} (requested by {} at {})",
+            self.kind, self.name, self.reason, self.requester, self.location
+        )
+    }
+}
+
+//lookups only take &self, so failures are collected through a RefCell
+pub struct AssetReport {
+    missing: RefCell<Vec<MissingAsset>>,
+    //assets that loaded but should be fixed in the pack, they dont count as missing
+    warnings: RefCell<Vec<MissingAsset>>,
+    requester: RefCell<String>,
+}
+impl AssetReport {
+    pub fn new() -> Self {
+        AssetReport {
+            missing: RefCell::new(Vec::new()),
+            warnings: RefCell::new(Vec::new()),
+            requester: RefCell::new(String::from("client")),
+        }
+    }
+    //everything recorded from now on is attributed to this requester
+    pub fn set_requester(&self, requester: String) {
+        *self.requester.borrow_mut() = requester;
+    }
+    pub fn record(
+        &self,
+        kind: AssetKind,
+        name: &str,
+        reason: String,
+        location: &'static Location<'static>,
+    ) {
+        self.push(&self.missing, kind, name, reason, location);
+    }
+    pub fn warn(
+        &self,
+        kind: AssetKind,
+        name: &str,
+        reason: String,
+        location: &'static Location<'static>,
+    ) {
+        self.push(&self.warnings, kind, name, reason, location);
+    }
+    fn push(
+        &self,
+        list: &RefCell<Vec<MissingAsset>>,
+        kind: AssetKind,
+        name: &str,
+        reason: String,
+        location: &'static Location<'static>,
+    ) {
+        let asset = MissingAsset {
+            kind,
+            name: name.to_string(),
+            reason,
+            requester: self.requester.borrow().clone(),
+            location,
+        };
+        let mut list = list.borrow_mut();
+        //gui lookups repeat every time an element is sent or drawn
+        if !list.contains(&asset) {
+            list.push(asset);
+        }
+    }
+    //warnings are left out, strict_assets only refuses to start on missing assets
+    pub fn is_empty(&self) -> bool {
+        self.missing.borrow().is_empty()
+    }
+    pub fn lines(&self) -> Vec<String> {
+        self.missing
+            .borrow()
+            .iter()
+            .map(|asset| asset.to_string())
+            .collect()
+    }
+    pub fn warning_lines(&self) -> Vec<String> {
+        self.warnings
+            .borrow()
+            .iter()
+            .map(|asset| asset.to_string())
+            .collect()
+    }
+    pub fn log_summary(&self) {
+        let warnings = self.warning_lines();
+        if !warnings.is_empty() {
+            println!("{} asset warnings:", warnings.len());
+            for line in warnings {
+                println!("  {}", line);
+            }
+        }
+        let lines = self.lines();
+        if lines.is_empty() {
+            println!("all assets found");
+            return;
+        }
+        println!("{} missing assets:", lines.len());
+        for line in lines {
+            println!("  {}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_dont_count_as_missing() {
+        let report = AssetReport::new();
+        report.warn(
+            AssetKind::Model,
+            "old",
+            String::from("has no bbm header"),
+            Location::caller(),
+        );
+        assert!(report.is_empty());
+        assert!(report.lines().is_empty());
+        assert_eq!(report.warning_lines().len(), 1);
+        report.record(
+            AssetKind::Texture,
+            "gone",
+            String::from("not found"),
+            Location::caller(),
+        );
+        assert!(!report.is_empty());
+        assert_eq!(report.lines().len(), 1);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::Write;
+
+use json::JsonValue;
+
+//has to fit next to texture_animations in the vertex shader uniforms
+pub const MAX_EFFECTS: usize = 64;
+
+//glsl function bodies, vertex snippets get position and return the moved position,
+//fragment snippets get color and return the new color, both also see amplitude, speed and time
+pub struct EffectDefinition {
+    pub name: String,
+    pub vertex: Option<String>,
+    pub fragment: Option<String>,
+    pub amplitude: f32,
+    pub speed: f32,
+}
+//an effect with its parameters, render_data of a block is the index + 1
+#[derive(Clone, Copy, PartialEq)]
+struct EffectInstance {
+    definition: usize,
+    amplitude: f32,
+    speed: f32,
+}
+pub struct EffectRegistry {
+    definitions: Vec<EffectDefinition>,
+    instances: Vec<EffectInstance>,
+}
+impl EffectRegistry {
+    pub fn new() -> Self {
+        let mut registry = EffectRegistry {
+            definitions: Vec::new(),
+            instances: Vec::new(),
+        };
+        for (name, vertex, amplitude, speed) in [
+            (
+                "water_surface",
+                include_str!("shaders/effects/water_surface.vert"),
+                0.1,
+                1.,
+            ),
+            (
+                "foliage_sway",
+                include_str!("shaders/effects/foliage_sway.vert"),
+                0.2,
+                1.,
+            ),
+            (
+                "jiggle",
+                include_str!("shaders/effects/jiggle.vert"),
+                0.03,
+                2.,
+            ),
+        ] {
+            registry.register(EffectDefinition {
+                name: name.to_string(),
+                vertex: Some(vertex.to_string()),
+                fragment: None,
+                amplitude,
+                speed,
+            });
+        }
+        registry
+    }
+    //effects from asset packs replace builtin ones with the same name
+    pub fn register(&mut self, definition: EffectDefinition) {
+        match self
+            .definitions
+            .iter_mut()
+            .find(|existing| existing.name == definition.name)
+        {
+            Some(existing) => *existing = definition,
+            None => self.definitions.push(definition),
+        }
+    }
+    pub fn set_defaults(&mut self, name: &str, json: &JsonValue) {
+        let definition = self
+            .definitions
+            .iter_mut()
+            .find(|definition| definition.name == name)
+            .unwrap_or_else(|| panic!("unknown effect {}", name));
+        definition.amplitude = json["amplitude"].as_f32().unwrap_or(definition.amplitude);
+        definition.speed = json["speed"].as_f32().unwrap_or(definition.speed);
+    }
+    //accepts "name" or {"name", "amplitude", "speed"}, 0 means no effect
+    pub fn resolve(&mut self, json: &JsonValue) -> u8 {
+        if json.is_null() {
+            return 0;
+        }
+        let name = json
+            .as_str()
+            .or(json["name"].as_str())
+            .expect("effect needs a name");
+        let definition = self
+            .definitions
+            .iter()
+            .position(|definition| definition.name == name)
+            .unwrap_or_else(|| panic!("unknown effect {}", name));
+        let instance = EffectInstance {
+            definition,
+            amplitude: json["amplitude"]
+                .as_f32()
+                .unwrap_or(self.definitions[definition].amplitude),
+            speed: json["speed"]
+                .as_f32()
+                .unwrap_or(self.definitions[definition].speed),
+        };
+        let index = match self.instances.iter().position(|other| *other == instance) {
+            Some(index) => index,
+            None => {
+                if self.instances.len() >= MAX_EFFECTS {
+                    panic!("more than {} effect variants", MAX_EFFECTS);
+                }
+                self.instances.push(instance);
+                self.instances.len() - 1
+            }
+        };
+        (index + 1) as u8
+    }
+    //the numbers content.json used before effects had names
+    pub fn resolve_legacy(&mut self, render_data: u8) -> u8 {
+        let name = match render_data {
+            0 => return 0,
+            1 => "water_surface",
+            2 => "foliage_sway",
+            4 => "jiggle",
+            //the old client ignored values it didnt know
+            _ => {
+                println!(
+                    "unknown render_data {}, drawing without an effect",
+                    render_data
+                );
+                return 0;
+            }
+        };
+        self.resolve(&JsonValue::from(name))
+    }
+    //definition index, amplitude, speed, unused
+    pub fn uniforms(&self) -> Vec<[f32; 4]> {
+        self.instances
+            .iter()
+            .map(|instance| {
+                [
+                    instance.definition as f32,
+                    instance.amplitude,
+                    instance.speed,
+                    0.,
+                ]
+            })
+            .collect()
+    }
+    //replaces the //effects line of both chunk shaders with the registered snippets
+    pub fn build_shaders(&self, vertex: &str, fragment: &str) -> (String, String) {
+        (
+            vertex.replace(
+                "//effects",
+                self.build_dispatch("vertex", "vec3", "position", |definition| {
+                    definition.vertex.as_ref()
+                })
+                .as_str(),
+            ),
+            fragment.replace(
+                "//effects",
+                self.build_dispatch("fragment", "vec4", "color", |definition| {
+                    definition.fragment.as_ref()
+                })
+                .as_str(),
+            ),
+        )
+    }
+    fn build_dispatch<F>(&self, stage: &str, value_type: &str, value: &str, snippet: F) -> String
+    where
+        F: Fn(&EffectDefinition) -> Option<&String>,
+    {
+        let mut source = String::new();
+        writeln!(source, "uniform vec4 effect_params[{}];", MAX_EFFECTS).unwrap();
+        let mut cases = String::new();
+        for (index, definition) in self.definitions.iter().enumerate() {
+            let Some(snippet) = snippet(definition) else {
+                continue;
+            };
+            writeln!(
+                source,
+                "//{}\n{} effect_{}_{}({} {}, float amplitude, float speed) {{\n{}\n}}",
+                definition.name, value_type, stage, index, value_type, value, snippet
+            )
+            .unwrap();
+            writeln!(
+                cases,
+                "  if(definition == {}) return effect_{}_{}({}, params.y, params.z);",
+                index, stage, index, value
+            )
+            .unwrap();
+        }
+        write!(
+            source,
+            "{} apply_{}_effect(int effect, {} {}) {{\n  if(effect == 0) return {};\n  vec4 params = effect_params[effect-1];\n  int definition = int(params.x);\n{}  return {};\n}}\n",
+            value_type, stage, value_type, value, value, cases, value
+        )
+        .unwrap();
+        source
+    }
+}
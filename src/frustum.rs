@@ -0,0 +1,97 @@
+use ultraviolet::{Mat4, Vec3, Vec4};
+
+//planes of the camera frustum in world space, normals point inside
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+impl Frustum {
+    //planes are read straight from the combined matrix, so orthographic projections work too
+    pub fn from_matrix(projection_view: &Mat4) -> Self {
+        let row = |i: usize| {
+            Vec4::new(
+                projection_view.cols[0][i],
+                projection_view.cols[1][i],
+                projection_view.cols[2][i],
+                projection_view.cols[3][i],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+            let length = plane.xyz().mag();
+            if length > 0. {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Frustum { planes }
+    }
+    //conservative, boxes near the corners of the frustum can pass while being outside
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            //corner furthest along the plane normal
+            let corner = Vec3::new(
+                if plane.x >= 0. { max.x } else { min.x },
+                if plane.y >= 0. { max.y } else { min.y },
+                if plane.z >= 0. { max.z } else { min.z },
+            );
+            plane.xyz().dot(corner) + plane.w >= 0.
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ultraviolet::projection::{orthographic_gl, perspective_gl};
+
+    //a block sized box centered on the point
+    fn block_at(frustum: &Frustum, x: f32, y: f32, z: f32) -> bool {
+        frustum.intersects_aabb(
+            Vec3::new(x - 0.5, y - 0.5, z - 0.5),
+            Vec3::new(x + 0.5, y + 0.5, z + 0.5),
+        )
+    }
+
+    #[test]
+    fn perspective_accepts_only_boxes_in_view() {
+        //the camera looks down -z from the origin
+        let frustum = Frustum::from_matrix(&perspective_gl(90f32.to_radians(), 1., 1., 100.));
+        assert!(block_at(&frustum, 0., 0., -10.));
+        assert!(block_at(&frustum, 9., 0., -10.));
+        assert!(!block_at(&frustum, 12., 0., -10.));
+        assert!(!block_at(&frustum, 0., -12., -10.));
+        assert!(!block_at(&frustum, 0., 0., 10.));
+        //between the camera and the near plane, and past the far plane
+        assert!(!block_at(&frustum, 0., 0., -0.2));
+        assert!(!block_at(&frustum, 0., 0., -101.));
+        assert!(block_at(&frustum, 0., 0., -100.));
+    }
+
+    #[test]
+    fn perspective_follows_view() {
+        let projection = perspective_gl(90f32.to_radians(), 1., 1., 100.);
+        let view = Mat4::look_at(
+            Vec3::new(50., 0., 0.),
+            Vec3::new(60., 0., 0.),
+            Vec3::new(0., 1., 0.),
+        );
+        let frustum = Frustum::from_matrix(&(projection * view));
+        assert!(block_at(&frustum, 60., 0., 0.));
+        assert!(!block_at(&frustum, 40., 0., 0.));
+        assert!(!block_at(&frustum, 0., 0., -10.));
+    }
+
+    #[test]
+    fn orthographic_accepts_only_boxes_in_view() {
+        let frustum = Frustum::from_matrix(&orthographic_gl(-10., 10., -10., 10., 1., 100.));
+        assert!(block_at(&frustum, 0., 0., -50.));
+        assert!(block_at(&frustum, 9.8, -9.8, -50.));
+        //unlike perspective the sides dont widen with distance
+        assert!(!block_at(&frustum, 12., 0., -90.));
+        assert!(!block_at(&frustum, 0., 12., -2.));
+        assert!(!block_at(&frustum, 0., 0., 5.));
+        assert!(!block_at(&frustum, 0., 0., -0.2));
+        assert!(!block_at(&frustum, 0., 0., -101.));
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     cell::{Ref, RefCell, RefMut},
-    collections::{BTreeSet, HashMap},
+    collections::{HashMap, VecDeque},
     hash::Hash,
     ops::AddAssign,
     os,
@@ -10,9 +10,15 @@ use std::{
 };
 
 use crate::{
-    glwrappers::{Vertex, VertexArray},
+    frustum::Frustum,
+    glwrappers::VertexArray,
+    lod::LodSettings,
+    meshing::{ChunkData, ChunkMesh, MeshSettings, MeshSnapshot, MeshWorkers},
     model::{self, Model},
+    palette::PalettedStorage,
+    tint::{BlockTint, ChunkBiomes},
     util::{self, *},
+    visibility::{self, FaceConnections},
     TextureAtlas,
 };
 use alto::{Alto, OutputDevice, Source};
@@ -344,8 +350,26 @@ pub struct DynamicBlockData {
 }
 
 pub struct Chunk<'a> {
-    blocks: [[[u32; 16]; 16]; 16],
-    light: [[[u16; 16]; 16]; 16],
+    data: Arc<ChunkData>,
+    //created with the first mesh, so chunks can exist without a gl context
+    buffers: Option<ChunkBuffers>,
+    //revision of the last mesh job, results of older jobs are stale
+    mesh_revision: u32,
+    //only one job per chunk is queued, changes made meanwhile wait for it to land
+    mesh_in_flight: bool,
+    //face pairs of the last mesh that can see each other, for occlusion culling
+    connections: FaceConnections,
+    //kept on the cpu so the triangles can be sorted back to front
+    transparent_vertices: Vec<glwrappers::Vertex>,
+    //camera position in chunk space at the last sort
+    transparent_sort_origin: Option<Vec3>,
+    //detail level the next mesh is built with
+    lod: u8,
+    position: ChunkPosition,
+    block_registry: &'a BlockRegistry,
+    pub dynamic_blocks: HashMap<BlockPosition, DynamicBlockData>,
+}
+struct ChunkBuffers {
     vao: glwrappers::VertexArray,
     vbo: glwrappers::Buffer,
     vertex_count: u32,
@@ -355,60 +379,25 @@ pub struct Chunk<'a> {
     foliage_vao: glwrappers::VertexArray,
     foliage_vbo: glwrappers::Buffer,
     foliage_vertex_count: u32,
-    position: ChunkPosition,
-    block_registry: &'a BlockRegistry,
-    front: Option<Rc<RefCell<Chunk<'a>>>>,
-    back: Option<Rc<RefCell<Chunk<'a>>>>,
-    left: Option<Rc<RefCell<Chunk<'a>>>>,
-    right: Option<Rc<RefCell<Chunk<'a>>>>,
-    up: Option<Rc<RefCell<Chunk<'a>>>>,
-    down: Option<Rc<RefCell<Chunk<'a>>>>,
-    pub dynamic_blocks: HashMap<BlockPosition, DynamicBlockData>,
 }
-impl<'a> Chunk<'a> {
-    fn set_neighbor_by_face(&mut self, face: &Face, chunk: Option<Rc<RefCell<Chunk<'a>>>>) {
-        match face {
-            Face::Front => self.front = chunk,
-            Face::Back => self.back = chunk,
-            Face::Left => self.left = chunk,
-            Face::Right => self.right = chunk,
-            Face::Up => self.up = chunk,
-            Face::Down => self.down = chunk,
+impl ChunkBuffers {
+    fn new() -> Self {
+        let (vao, vbo) = ChunkBuffers::create_vertex_array();
+        let (transparent_vao, transparent_vbo) = ChunkBuffers::create_vertex_array();
+        let (foliage_vao, foliage_vbo) = ChunkBuffers::create_vertex_array();
+        ChunkBuffers {
+            vao,
+            vbo,
+            vertex_count: 0,
+            transparent_vao,
+            transparent_vbo,
+            transparent_vertex_count: 0,
+            foliage_vao,
+            foliage_vbo,
+            foliage_vertex_count: 0,
         }
     }
-    pub fn new(
-        position: ChunkPosition,
-        block_registry: &'a BlockRegistry,
-        blocks: [[[u32; 16]; 16]; 16],
-        world: &mut World,
-    ) -> Self {
-        let mut dynamic_blocks = HashMap::new();
-        for x in 0..16 {
-            for y in 0..16 {
-                for z in 0..16 {
-                    let id = blocks[x as usize][y as usize][z as usize];
-                    let block = block_registry.get_block(id);
-                    let block_position = BlockPosition {
-                        x: position.x * 16 + x,
-                        y: position.y * 16 + y,
-                        z: position.z * 16 + z,
-                    };
-                    if block.is_light_emmiting() {
-                        world.light_updates.insert(block_position);
-                    }
-                    if block.dynamic.is_some() {
-                        dynamic_blocks.insert(
-                            block_position,
-                            DynamicBlockData {
-                                id,
-                                animation: None,
-                                items: HashMap::new(),
-                            },
-                        );
-                    }
-                }
-            }
-        }
+    fn create_vertex_array() -> (glwrappers::VertexArray, glwrappers::Buffer) {
         let vao = glwrappers::VertexArray::new().expect("couldnt create vao for chunk");
         vao.bind();
         let vbo = glwrappers::Buffer::new(glwrappers::BufferType::Array)
@@ -453,129 +442,105 @@ impl<'a> Chunk<'a> {
                     .unwrap(),
                 21 as *const _,
             );
-            ogl33::glEnableVertexAttribArray(3);
-            ogl33::glEnableVertexAttribArray(2);
-            ogl33::glEnableVertexAttribArray(1);
-            ogl33::glEnableVertexAttribArray(0);
-        }
-        let transparent_vao = glwrappers::VertexArray::new().expect("couldnt create vao for chunk");
-        transparent_vao.bind();
-        let transparent_vbo = glwrappers::Buffer::new(glwrappers::BufferType::Array)
-            .expect("couldnt create vbo for chunk");
-        transparent_vbo.bind();
-        unsafe {
-            ogl33::glVertexAttribPointer(
-                0,
-                3,
-                ogl33::GL_FLOAT,
-                ogl33::GL_FALSE,
-                std::mem::size_of::<glwrappers::Vertex>()
-                    .try_into()
-                    .unwrap(),
-                0 as *const _,
-            );
             ogl33::glVertexAttribPointer(
+                7,
                 1,
-                2,
-                ogl33::GL_FLOAT,
+                ogl33::GL_UNSIGNED_BYTE,
                 ogl33::GL_FALSE,
                 std::mem::size_of::<glwrappers::Vertex>()
                     .try_into()
                     .unwrap(),
-                12 as *const _,
+                35 as *const _,
             );
             ogl33::glVertexAttribIPointer(
-                2,
-                1,
-                ogl33::GL_BYTE,
-                std::mem::size_of::<glwrappers::Vertex>()
-                    .try_into()
-                    .unwrap(),
-                20 as *const _,
-            );
-            ogl33::glVertexAttribIPointer(
-                3,
+                4,
                 1,
-                ogl33::GL_SHORT,
+                ogl33::GL_UNSIGNED_BYTE,
                 std::mem::size_of::<glwrappers::Vertex>()
                     .try_into()
                     .unwrap(),
-                21 as *const _,
+                23 as *const _,
             );
-            ogl33::glEnableVertexAttribArray(3);
-            ogl33::glEnableVertexAttribArray(2);
-            ogl33::glEnableVertexAttribArray(1);
-            ogl33::glEnableVertexAttribArray(0);
-        }
-        let foliage_vao = glwrappers::VertexArray::new().expect("couldnt create vao for chunk");
-        foliage_vao.bind();
-        let foliage_vbo = glwrappers::Buffer::new(glwrappers::BufferType::Array)
-            .expect("couldnt create vbo for chunk");
-        foliage_vbo.bind();
-        unsafe {
             ogl33::glVertexAttribPointer(
-                0,
+                5,
                 3,
-                ogl33::GL_FLOAT,
-                ogl33::GL_FALSE,
+                ogl33::GL_UNSIGNED_BYTE,
+                ogl33::GL_TRUE,
                 std::mem::size_of::<glwrappers::Vertex>()
                     .try_into()
                     .unwrap(),
-                0 as *const _,
+                24 as *const _,
             );
             ogl33::glVertexAttribPointer(
-                1,
-                2,
-                ogl33::GL_FLOAT,
-                ogl33::GL_FALSE,
-                std::mem::size_of::<glwrappers::Vertex>()
-                    .try_into()
-                    .unwrap(),
-                12 as *const _,
-            );
-            ogl33::glVertexAttribIPointer(
-                2,
-                1,
-                ogl33::GL_BYTE,
-                std::mem::size_of::<glwrappers::Vertex>()
-                    .try_into()
-                    .unwrap(),
-                20 as *const _,
-            );
-            ogl33::glVertexAttribIPointer(
-                3,
-                1,
-                ogl33::GL_SHORT,
+                6,
+                4,
+                ogl33::GL_UNSIGNED_SHORT,
+                ogl33::GL_TRUE,
                 std::mem::size_of::<glwrappers::Vertex>()
                     .try_into()
                     .unwrap(),
-                21 as *const _,
+                27 as *const _,
             );
+            ogl33::glEnableVertexAttribArray(7);
+            ogl33::glEnableVertexAttribArray(6);
+            ogl33::glEnableVertexAttribArray(5);
+            ogl33::glEnableVertexAttribArray(4);
             ogl33::glEnableVertexAttribArray(3);
             ogl33::glEnableVertexAttribArray(2);
             ogl33::glEnableVertexAttribArray(1);
             ogl33::glEnableVertexAttribArray(0);
         }
+        (vao, vbo)
+    }
+}
+impl<'a> Chunk<'a> {
+    pub fn new(
+        position: ChunkPosition,
+        block_registry: &'a BlockRegistry,
+        blocks: [[[u32; 16]; 16]; 16],
+        world: &mut World,
+    ) -> Self {
+        let mut dynamic_blocks = HashMap::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    let id = blocks[x as usize][y as usize][z as usize];
+                    let block = block_registry.get_block(id);
+                    let block_position = BlockPosition {
+                        x: position.x * 16 + x,
+                        y: position.y * 16 + y,
+                        z: position.z * 16 + z,
+                    };
+                    if block.is_light_emmiting() {
+                        world.light_spreads.push_back(block_position);
+                    }
+                    if block.dynamic.is_some() {
+                        dynamic_blocks.insert(
+                            block_position,
+                            DynamicBlockData {
+                                id,
+                                animation: None,
+                                items: HashMap::new(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
         Chunk {
-            blocks,
-            light: [[[15 << 12; 16]; 16]; 16],
-            vao,
-            vbo,
-            vertex_count: 0,
+            data: Arc::new(ChunkData {
+                blocks: PalettedStorage::from_array(&blocks),
+                light: PalettedStorage::uniform(0),
+            }),
+            buffers: None,
+            mesh_revision: 0,
+            mesh_in_flight: false,
+            connections: FaceConnections::all(),
+            transparent_vertices: Vec::new(),
+            transparent_sort_origin: None,
+            lod: 0,
             position,
             block_registry,
-            transparent_vbo,
-            transparent_vao,
-            transparent_vertex_count: 0,
-            foliage_vbo,
-            foliage_vao,
-            foliage_vertex_count: 0,
-            front: None,
-            back: None,
-            left: None,
-            right: None,
-            up: None,
-            down: None,
             dynamic_blocks,
         }
     }
@@ -586,7 +551,7 @@ impl<'a> Chunk<'a> {
             z: (self.position.z * 16) + z as i32,
         };
         self.dynamic_blocks.remove(&position);
-        self.blocks[x as usize][y as usize][z as usize] = block_type;
+        Arc::make_mut(&mut self.data).set_block(x as usize, y as usize, z as usize, block_type);
         world.chunk_mesh_updates.insert(self.position);
         if self.block_registry.get_block(block_type).dynamic.is_some() {
             self.dynamic_blocks.insert(
@@ -603,517 +568,123 @@ impl<'a> Chunk<'a> {
         world.chunk_mesh_updates.insert(self.position);
     }
     pub fn get_block(&self, x: u8, y: u8, z: u8) -> u32 {
-        return self.blocks[x as usize][y as usize][z as usize];
+        return self.data.get_block(x as usize, y as usize, z as usize);
+    }
+    //all four channels as stored, r, g and b in the low nibbles and sky light in the high one
+    fn get_packed_light(&self, x: u8, y: u8, z: u8) -> u16 {
+        self.data.get_light(x as usize, y as usize, z as usize)
+    }
+    fn set_packed_light(&mut self, x: u8, y: u8, z: u8, light: u16) {
+        Arc::make_mut(&mut self.data).set_light(x as usize, y as usize, z as usize, light);
+    }
+    pub fn memory_usage(&self) -> usize {
+        self.data.memory_usage()
     }
     pub fn get_light(&self, x: u8, y: u8, z: u8) -> (u8, u8, u8) {
-        let light = self.light[x as usize][y as usize][z as usize];
+        let light = self.get_packed_light(x, y, z);
         (
             (light & 15) as u8,
             ((light >> 4) & 15) as u8,
             ((light >> 8) & 15) as u8,
-            //((light >> 12) & 15) as u8,
         )
     }
-    fn rebuild_chunk_mesh(&mut self, this: Rc<RefCell<Chunk<'a>>>, world: &mut World<'a>) {
-        if self.front.is_none()
-            || self.back.is_none()
-            || self.left.is_none()
-            || self.right.is_none()
-            || self.up.is_none()
-            || self.down.is_none()
-        {
+    pub fn get_sky_light(&self, x: u8, y: u8, z: u8) -> u8 {
+        (self.get_packed_light(x, y, z) >> 12) as u8
+    }
+    //uploads a mesh built by the workers, returns whether there is something solid and transparent to draw
+    fn upload_mesh(&mut self, mesh: ChunkMesh) -> (bool, bool) {
+        self.connections = mesh.connections;
+        let buffers = self.buffers.get_or_insert_with(ChunkBuffers::new);
+        buffers.vertex_count = mesh.vertices.len() as u32;
+        buffers
+            .vbo
+            .upload_data(bytemuck::cast_slice(&mesh.vertices), ogl33::GL_STATIC_DRAW);
+        buffers.transparent_vertex_count = mesh.transparent_vertices.len() as u32;
+        buffers.transparent_vbo.upload_data(
+            bytemuck::cast_slice(&mesh.transparent_vertices),
+            ogl33::GL_DYNAMIC_DRAW,
+        );
+        self.transparent_vertices = mesh.transparent_vertices;
+        self.transparent_sort_origin = None;
+        buffers.foliage_vertex_count = mesh.foliage_vertices.len() as u32;
+        buffers.foliage_vbo.upload_data(
+            bytemuck::cast_slice(&mesh.foliage_vertices),
+            ogl33::GL_STATIC_DRAW,
+        );
+        (
+            buffers.vertex_count > 0 || buffers.foliage_vertex_count > 0,
+            buffers.transparent_vertex_count > 0,
+        )
+    }
+    fn center(&self) -> Vec3 {
+        Vec3::new(
+            (self.position.x * 16 + 8) as f32,
+            (self.position.y * 16 + 8) as f32,
+            (self.position.z * 16 + 8) as f32,
+        )
+    }
+    //new meshes are always sorted, after that only chunks close to the camera are resorted
+    //once it moved a block, far away the order barely changes
+    pub fn sort_transparent(&mut self, camera: Vec3) {
+        if self.buffers.is_none() || self.transparent_vertices.is_empty() {
             return;
         }
-        let front_chunk = self.front.as_deref().unwrap().borrow();
-        let back_chunk = self.back.as_deref().unwrap().borrow();
-        let left_chunk = self.left.as_deref().unwrap().borrow();
-        let right_chunk = self.right.as_deref().unwrap().borrow();
-        let up_chunk = self.up.as_deref().unwrap().borrow();
-        let down_chunk = self.down.as_deref().unwrap().borrow();
-
-        let mut vertices: Vec<glwrappers::Vertex> = Vec::new();
-        let mut transparent_vertices: Vec<glwrappers::Vertex> = Vec::new();
-        let mut foliage_vertices: Vec<glwrappers::Vertex> = Vec::new();
-        for bx in 0..16i32 {
-            let x = bx as f32;
-            for by in 0..16i32 {
-                let y = by as f32;
-                for bz in 0..16i32 {
-                    let z = bz as f32;
-                    let block_id = self.blocks[bx as usize][by as usize][bz as usize];
-                    let block = self.block_registry.get_block(block_id);
-                    let position = BlockPosition {
-                        x: bx,
-                        y: by,
-                        z: bz,
-                    };
-                    match &block.render_type {
-                        BlockRenderType::Air => {}
-                        BlockRenderType::Cube(transparent, north, south, right, left, up, down) => {
-                            for face in Face::all() {
-                                let face_offset = face.get_offset();
-                                let neighbor_pos = position + face_offset;
-                                let original_offset_in_chunk = position.chunk_offset();
-                                let offset_in_chunk = neighbor_pos.chunk_offset();
-                                let (neighbor_block, light) = match (BlockPosition {
-                                    x: original_offset_in_chunk.0 as i32 + face_offset.x,
-                                    y: original_offset_in_chunk.1 as i32 + face_offset.y,
-                                    z: original_offset_in_chunk.2 as i32 + face_offset.z,
-                                }
-                                .offset_from_origin_chunk())
-                                {
-                                    Some(Face::Front) => (
-                                        front_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        front_chunk.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                    Some(Face::Back) => (
-                                        back_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        back_chunk.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                    Some(Face::Left) => (
-                                        left_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        left_chunk.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                    Some(Face::Right) => (
-                                        right_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        right_chunk.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                    Some(Face::Up) => (
-                                        up_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        up_chunk.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                    Some(Face::Down) => (
-                                        down_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        down_chunk.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                    None => (
-                                        self.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                        self.light[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize],
-                                    ),
-                                };
-                                let neighbor_block = self.block_registry.get_block(neighbor_block);
-                                let neighbor_side_full = neighbor_block
-                                    .is_face_full(&face.opposite())
-                                    && !(neighbor_block.is_transparent()
-                                        && !block.is_transparent());
-                                let texture = match face {
-                                    Face::Front => north,
-                                    Face::Back => south,
-                                    Face::Right => right,
-                                    Face::Left => left,
-                                    Face::Up => up,
-                                    Face::Down => down,
-                                };
-                                if !neighbor_side_full {
-                                    let face_vertices = face.get_vertices();
-                                    let uv = texture.get_coords();
-                                    let vertices = if *transparent {
-                                        &mut transparent_vertices
-                                    } else {
-                                        &mut vertices
-                                    };
-                                    let uv0 = face_vertices[0].1.map(uv);
-                                    let uv1 = face_vertices[1].1.map(uv);
-                                    let uv2 = face_vertices[2].1.map(uv);
-                                    let uv3 = face_vertices[3].1.map(uv);
-
-                                    vertices.push(glwrappers::Vertex {
-                                        x: face_vertices[0].0.x + x,
-                                        y: face_vertices[0].0.y + y,
-                                        z: face_vertices[0].0.z + z,
-                                        u: uv0.0,
-                                        v: uv0.1,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                    vertices.push(glwrappers::Vertex {
-                                        x: face_vertices[1].0.x + x,
-                                        y: face_vertices[1].0.y + y,
-                                        z: face_vertices[1].0.z + z,
-                                        u: uv1.0,
-                                        v: uv1.1,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                    vertices.push(glwrappers::Vertex {
-                                        x: face_vertices[2].0.x + x,
-                                        y: face_vertices[2].0.y + y,
-                                        z: face_vertices[2].0.z + z,
-                                        u: uv2.0,
-                                        v: uv2.1,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                    vertices.push(glwrappers::Vertex {
-                                        x: face_vertices[2].0.x + x,
-                                        y: face_vertices[2].0.y + y,
-                                        z: face_vertices[2].0.z + z,
-                                        u: uv2.0,
-                                        v: uv2.1,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                    vertices.push(glwrappers::Vertex {
-                                        x: face_vertices[3].0.x + x,
-                                        y: face_vertices[3].0.y + y,
-                                        z: face_vertices[3].0.z + z,
-                                        u: uv3.0,
-                                        v: uv3.1,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                    vertices.push(glwrappers::Vertex {
-                                        x: face_vertices[0].0.x + x,
-                                        y: face_vertices[0].0.y + y,
-                                        z: face_vertices[0].0.z + z,
-                                        u: uv0.0,
-                                        v: uv0.1,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                }
-                            }
-                        }
-                        BlockRenderType::StaticModel(
-                            transparent,
-                            model,
-                            _,
-                            _,
-                            _,
-                            _,
-                            _,
-                            _,
-                            connections,
-                            foliage,
-                        ) => {
-                            let original_offset_in_chunk = position.chunk_offset();
-                            let light = self.light[original_offset_in_chunk.0 as usize]
-                                [original_offset_in_chunk.1 as usize]
-                                [original_offset_in_chunk.2 as usize];
-                            let vertices = if *transparent {
-                                &mut transparent_vertices
-                            } else if *foliage {
-                                &mut foliage_vertices
-                            } else {
-                                &mut vertices
-                            };
-                            for face in Face::all() {
-                                let face_offset = face.get_offset();
-                                let neighbor_pos = position + face_offset;
-                                let offset_in_chunk = neighbor_pos.chunk_offset();
-                                let neighbor_block = match (BlockPosition {
-                                    x: original_offset_in_chunk.0 as i32 + face_offset.x,
-                                    y: original_offset_in_chunk.1 as i32 + face_offset.y,
-                                    z: original_offset_in_chunk.2 as i32 + face_offset.z,
-                                }
-                                .offset_from_origin_chunk())
-                                {
-                                    Some(Face::Front) => {
-                                        front_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                    Some(Face::Back) => {
-                                        back_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                    Some(Face::Left) => {
-                                        left_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                    Some(Face::Right) => {
-                                        right_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                    Some(Face::Up) => {
-                                        up_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                    Some(Face::Down) => {
-                                        down_chunk.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                    None => {
-                                        self.blocks[offset_in_chunk.0 as usize]
-                                            [offset_in_chunk.1 as usize]
-                                            [offset_in_chunk.2 as usize]
-                                    }
-                                };
-                                if let Some(connection) =
-                                    connections.by_face(face).get(&neighbor_block)
-                                {
-                                    connection.add_vertices_simple(
-                                        &mut |pos, u, v| {
-                                            vertices.push(Vertex {
-                                                x: pos.x + 0.5,
-                                                y: pos.y,
-                                                z: pos.z + 0.5,
-                                                u,
-                                                v,
-                                                render_data: block.render_data,
-                                                light,
-                                            });
-                                            self.vertex_count += 1;
-                                        },
-                                        None,
-                                        Vec3 {
-                                            x: bx as f32,
-                                            y: by as f32,
-                                            z: bz as f32,
-                                        },
-                                        None,
-                                    );
-                                }
-                            }
-                            model.add_vertices_simple(
-                                &mut |pos, u, v| {
-                                    vertices.push(Vertex {
-                                        x: pos.x + 0.5,
-                                        y: pos.y,
-                                        z: pos.z + 0.5,
-                                        u,
-                                        v,
-                                        render_data: block.render_data,
-                                        light,
-                                    });
-                                    self.vertex_count += 1;
-                                },
-                                None,
-                                Vec3 {
-                                    x: bx as f32,
-                                    y: by as f32,
-                                    z: bz as f32,
-                                },
-                                None,
-                            );
-                        }
-                        BlockRenderType::Foliage(texture1, texture2, texture3, texture4) => {
-                            let original_offset_in_chunk = position.chunk_offset();
-                            let light = self.light[original_offset_in_chunk.0 as usize]
-                                [original_offset_in_chunk.1 as usize]
-                                [original_offset_in_chunk.2 as usize];
-                            let mut face_creator =
-                                |p1: Position,
-                                 p2: Position,
-                                 p3: Position,
-                                 p4: Position,
-                                 uv: (f32, f32, f32, f32),
-                                 render_data: u8,
-                                 light: u16| {
-                                    let v1 = Vertex {
-                                        x: p1.x,
-                                        y: p1.y,
-                                        z: p1.z,
-                                        u: uv.0,
-                                        v: uv.1,
-                                        render_data,
-                                        light,
-                                    };
-                                    let v2 = Vertex {
-                                        x: p2.x,
-                                        y: p2.y,
-                                        z: p2.z,
-                                        u: uv.2,
-                                        v: uv.1,
-                                        render_data,
-                                        light,
-                                    };
-                                    let v3 = Vertex {
-                                        x: p3.x,
-                                        y: p3.y,
-                                        z: p3.z,
-                                        u: uv.2,
-                                        v: uv.3,
-                                        render_data,
-                                        light,
-                                    };
-                                    let v4 = Vertex {
-                                        x: p4.x,
-                                        y: p4.y,
-                                        z: p4.z,
-                                        u: uv.0,
-                                        v: uv.3,
-                                        render_data,
-                                        light,
-                                    };
-                                    foliage_vertices.push(v1);
-                                    foliage_vertices.push(v2);
-                                    foliage_vertices.push(v3);
-                                    foliage_vertices.push(v3);
-                                    foliage_vertices.push(v4);
-                                    foliage_vertices.push(v1);
-                                };
-                            if let Some(texture1) = texture1 {
-                                face_creator.call_mut((
-                                    Position {
-                                        x: x + 0.01,
-                                        y: y + 0.99,
-                                        z,
-                                    },
-                                    Position {
-                                        x: x + 0.99,
-                                        y: y + 0.99,
-                                        z: z + 0.99,
-                                    },
-                                    Position {
-                                        x: x + 0.99,
-                                        y,
-                                        z: z + 0.99,
-                                    },
-                                    Position { x: x + 0.01, y, z },
-                                    texture1.get_coords(),
-                                    block.render_data,
-                                    light,
-                                ));
-                            }
-                            if let Some(texture2) = texture2 {
-                                face_creator.call_mut((
-                                    Position {
-                                        x: x + 0.99,
-                                        y: y + 0.99,
-                                        z,
-                                    },
-                                    Position {
-                                        x: x + 0.01,
-                                        y: y + 0.99,
-                                        z: z + 0.99,
-                                    },
-                                    Position {
-                                        x: x + 0.01,
-                                        y,
-                                        z: z + 1.,
-                                    },
-                                    Position { x: x + 0.99, y, z },
-                                    texture2.get_coords(),
-                                    block.render_data,
-                                    light,
-                                ));
-                            }
-                            if let Some(texture3) = texture3 {
-                                face_creator.call_mut((
-                                    Position {
-                                        x: x + 0.5,
-                                        y: y + 0.99,
-                                        z,
-                                    },
-                                    Position {
-                                        x: x + 0.5,
-                                        y: y + 0.99,
-                                        z: z + 0.99,
-                                    },
-                                    Position {
-                                        x: x + 0.5,
-                                        y,
-                                        z: z + 0.99,
-                                    },
-                                    Position { x: x + 0.5, y, z },
-                                    texture3.get_coords(),
-                                    block.render_data,
-                                    light,
-                                ));
-                            }
-                            if let Some(texture4) = texture4 {
-                                face_creator.call_mut((
-                                    Position {
-                                        x: x + 0.1,
-                                        y: y + 0.99,
-                                        z: z + 0.5,
-                                    },
-                                    Position {
-                                        x: x + 0.99,
-                                        y: y + 0.99,
-                                        z: z + 0.5,
-                                    },
-                                    Position {
-                                        x: x + 0.99,
-                                        y,
-                                        z: z + 0.5,
-                                    },
-                                    Position {
-                                        x: x + 0.01,
-                                        y,
-                                        z: z + 0.5,
-                                    },
-                                    texture4.get_coords(),
-                                    block.render_data,
-                                    light,
-                                ));
-                            }
-                        }
-                    }
-                }
+        let camera_in_chunk = camera - (self.center() - Vec3::broadcast(8.));
+        if let Some(origin) = self.transparent_sort_origin {
+            let near = (camera_in_chunk - Vec3::broadcast(8.)).mag() < 32.;
+            if !near || (origin - camera_in_chunk).mag_sq() < 1. {
+                return;
             }
         }
-        self.vertex_count = vertices.len() as u32;
-        self.vbo
-            .upload_data(bytemuck::cast_slice(&vertices), ogl33::GL_STATIC_DRAW);
-        self.transparent_vertex_count = transparent_vertices.len() as u32;
-        self.transparent_vbo.upload_data(
-            bytemuck::cast_slice(&transparent_vertices),
-            ogl33::GL_STATIC_DRAW,
+        self.transparent_sort_origin = Some(camera_in_chunk);
+        let mut triangles: Vec<(f32, [glwrappers::Vertex; 3])> = self
+            .transparent_vertices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let center = triangle.iter().fold(Vec3::zero(), |sum, vertex| {
+                    sum + Vec3::new(vertex.x, vertex.y, vertex.z)
+                }) / 3.;
+                (
+                    (center - camera_in_chunk).mag_sq(),
+                    [triangle[0], triangle[1], triangle[2]],
+                )
+            })
+            .collect();
+        triangles.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.transparent_vertices = triangles
+            .into_iter()
+            .flat_map(|(_, triangle)| triangle)
+            .collect();
+        let buffers = self.buffers.as_mut().unwrap();
+        buffers.transparent_vbo.upload_data(
+            bytemuck::cast_slice(&self.transparent_vertices),
+            ogl33::GL_DYNAMIC_DRAW,
         );
-        self.foliage_vertex_count = foliage_vertices.len() as u32;
-        self.foliage_vbo.upload_data(
-            bytemuck::cast_slice(&foliage_vertices),
-            ogl33::GL_STATIC_DRAW,
+    }
+    //padded by a block so vertex effects moving geometry past the border don't pop
+    pub fn in_frustum(&self, frustum: &Frustum) -> bool {
+        let min = Vec3::new(
+            (self.position.x * 16) as f32,
+            (self.position.y * 16) as f32,
+            (self.position.z * 16) as f32,
         );
-
-        if self.vertex_count > 0 || self.foliage_vertex_count > 0 {
-            world.solid_chunks.insert(self.position, this.clone());
-        } else {
-            world.solid_chunks.remove(&self.position);
-        }
-        if self.transparent_vertex_count > 0 {
-            world.transparent_chunks.insert(self.position, this.clone());
-        } else {
-            world.transparent_chunks.remove(&self.position);
-        }
+        frustum.intersects_aabb(min - Vec3::one(), min + Vec3::broadcast(17.))
     }
     pub fn render(
         &mut self,
         shader: &glwrappers::Shader,
         render_foliage: bool,
-        rendered_chunks_stat: &mut (i32, i32, i32, i32, i32),
+        rendered_chunks_stat: &mut (i32, i32, i32, i32, i32, i32, i32),
     ) {
+        let Some(buffers) = &self.buffers else {
+            return;
+        };
         /*if self.modified {
             self.modified = !self.rebuild_chunk_mesh();
         }*/
         if true {
             /* !self.modified*/
-            if self.vertex_count != 0 || (self.foliage_vertex_count != 0 && render_foliage) {
+            if buffers.vertex_count != 0 || (buffers.foliage_vertex_count != 0 && render_foliage) {
                 shader.set_uniform_matrix(
                     shader
                         .get_uniform_location("model\0")
@@ -1125,20 +696,24 @@ impl<'a> Chunk<'a> {
                     }),
                 );
             }
-            if self.vertex_count != 0 {
+            if buffers.vertex_count != 0 {
                 rendered_chunks_stat.0 += 1;
-                self.vao.bind();
+                buffers.vao.bind();
                 unsafe {
-                    ogl33::glDrawArrays(ogl33::GL_TRIANGLES, 0, self.vertex_count as i32);
+                    ogl33::glDrawArrays(ogl33::GL_TRIANGLES, 0, buffers.vertex_count as i32);
                 }
             }
-            if self.foliage_vertex_count != 0 && render_foliage {
+            if buffers.foliage_vertex_count != 0 && render_foliage {
                 rendered_chunks_stat.2 += 1;
-                self.foliage_vao.bind();
+                buffers.foliage_vao.bind();
                 unsafe {
                     ogl33::glDisable(GL_CULL_FACE);
 
-                    ogl33::glDrawArrays(ogl33::GL_TRIANGLES, 0, self.foliage_vertex_count as i32);
+                    ogl33::glDrawArrays(
+                        ogl33::GL_TRIANGLES,
+                        0,
+                        buffers.foliage_vertex_count as i32,
+                    );
                     ogl33::glEnable(GL_CULL_FACE);
                 }
             }
@@ -1147,9 +722,12 @@ impl<'a> Chunk<'a> {
     pub fn render_transparent(
         &self,
         shader: &glwrappers::Shader,
-        rendered_chunks_stat: &mut (i32, i32, i32, i32, i32),
+        rendered_chunks_stat: &mut (i32, i32, i32, i32, i32, i32, i32),
     ) {
-        if self.transparent_vertex_count != 0 {
+        let Some(buffers) = &self.buffers else {
+            return;
+        };
+        if buffers.transparent_vertex_count != 0 {
             rendered_chunks_stat.1 += 1;
             shader.set_uniform_matrix(
                 shader
@@ -1161,9 +739,13 @@ impl<'a> Chunk<'a> {
                     z: (self.position.z * 16) as f32,
                 }),
             );
-            self.transparent_vao.bind();
+            buffers.transparent_vao.bind();
             unsafe {
-                ogl33::glDrawArrays(ogl33::GL_TRIANGLES, 0, self.transparent_vertex_count as i32);
+                ogl33::glDrawArrays(
+                    ogl33::GL_TRIANGLES,
+                    0,
+                    buffers.transparent_vertex_count as i32,
+                );
             }
         }
     }
@@ -1328,11 +910,14 @@ impl BlockRenderType {
 pub struct Block {
     pub render_type: BlockRenderType,
     pub dynamic: Option<Model>,
+    //index into the effect registry, 0 for none
     pub render_data: u8,
     pub fluid: bool,
     pub no_collision: bool,
     pub selectable: bool,
     pub light: (u8, u8, u8),
+    pub rotation: BlockRotation,
+    pub tint: BlockTint,
 }
 impl Block {
     pub fn new_air() -> Self {
@@ -1344,6 +929,8 @@ impl Block {
             no_collision: true,
             light: (0, 0, 0),
             selectable: false,
+            rotation: BlockRotation::default(),
+            tint: BlockTint::none(),
         }
     }
     pub fn is_light_emmiting(&self) -> bool {
@@ -1357,7 +944,9 @@ impl Block {
             BlockRenderType::Foliage(_, _, _, _) => false,
         }
     }
+    //face is in world space, variants are rotated back to the faces of their model
     pub fn is_face_full(&self, face: &Face) -> bool {
+        let face = self.rotation.unrotate_face(*face);
         match self.render_type {
             BlockRenderType::Air => false,
             BlockRenderType::Cube(_, _, _, _, _, _, _) => true,
@@ -1393,7 +982,47 @@ impl BlockRegistry {
         &self.blocks[id as usize]
     }
 }
-#[derive(Clone, Copy, Debug)]
+#[cfg(test)]
+impl BlockRegistry {
+    pub const STONE: u32 = 1;
+    //same as stone, but with a vertex effect, so it's meshed face by face
+    pub const EFFECT_STONE: u32 = 2;
+    pub const GLASS: u32 = 3;
+    pub const TORCH: u32 = 4;
+    //a few blocks to mesh and light chunks with, no content or gl needed
+    pub fn for_tests() -> Self {
+        let cube = |transparent: bool, render_data: u8| Block {
+            render_type: BlockRenderType::Cube(
+                transparent,
+                AtlassedTexture::empty(),
+                AtlassedTexture::empty(),
+                AtlassedTexture::empty(),
+                AtlassedTexture::empty(),
+                AtlassedTexture::empty(),
+                AtlassedTexture::empty(),
+            ),
+            render_data,
+            no_collision: false,
+            selectable: true,
+            ..Block::new_air()
+        };
+        BlockRegistry {
+            blocks: vec![
+                Block::new_air(),
+                cube(false, 0),
+                cube(false, 1),
+                cube(true, 0),
+                Block {
+                    render_type: BlockRenderType::Foliage(None, None, None, None),
+                    light: (14, 14, 14),
+                    selectable: true,
+                    ..Block::new_air()
+                },
+            ],
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AtlassedTexture {
     pub x: u32,
     pub y: u32,
@@ -1401,6 +1030,7 @@ pub struct AtlassedTexture {
     pub h: u32,
     pub atlas_w: u32,
     pub atlas_h: u32,
+    pub animation: Option<TextureAnimation>,
 }
 impl AtlassedTexture {
     pub fn empty() -> AtlassedTexture {
@@ -1411,6 +1041,30 @@ impl AtlassedTexture {
             h: 0,
             atlas_w: 1,
             atlas_h: 1,
+            animation: None,
+        }
+    }
+    //0 means not animated, otherwise index into the texture_animations uniform plus one
+    pub fn animation_id(&self) -> u8 {
+        self.animation.map(|animation| animation.id).unwrap_or(0)
+    }
+    //height of the whole strip, animated textures only expose their first frame through h
+    pub fn strip_height(&self) -> u32 {
+        self.h
+            * self
+                .animation
+                .map(|animation| animation.frames)
+                .unwrap_or(1)
+    }
+    //frame for cpu side rendering, no interpolation is done here
+    pub fn frame_at(&self, time: f32) -> AtlassedTexture {
+        match self.animation {
+            Some(animation) => AtlassedTexture {
+                y: self.y + (animation.frame_at(time) * self.h),
+                animation: None,
+                ..*self
+            },
+            None => *self,
         }
     }
     pub fn get_coords(&self) -> (f32, f32, f32, f32) {
@@ -1434,24 +1088,59 @@ impl AtlassedTexture {
         )
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureAnimation {
+    pub id: u8,
+    pub frames: u32,
+    pub frame_time: f32,
+    pub interpolate: bool,
+}
+impl TextureAnimation {
+    pub fn from_json(json: &JsonValue, id: u8, frames: u32) -> TextureAnimation {
+        TextureAnimation {
+            id,
+            frames: json["frames"].as_u32().unwrap_or(frames).max(1),
+            frame_time: json["frame_time"].as_f32().unwrap_or(0.1).max(0.001),
+            interpolate: json["interpolate"].as_bool().unwrap_or(false),
+        }
+    }
+    pub fn frame_at(&self, time: f32) -> u32 {
+        ((time / self.frame_time).floor() as u32) % self.frames
+    }
+}
 
+const LIGHT_UPDATES_PER_FRAME: u32 = 20000;
 pub struct World<'a> {
     pub chunks: IndexMap<ChunkPosition, Rc<RefCell<Chunk<'a>>>>,
     block_registry: &'a BlockRegistry,
-    pub light_updates: BTreeSet<BlockPosition>,
+    //light is darkened first and then spread again, removals are per channel
+    light_removals: VecDeque<(BlockPosition, usize, u8)>,
+    light_spreads: VecDeque<BlockPosition>,
     pub chunk_mesh_updates: FxHashSet<ChunkPosition>,
     pub solid_chunks: FxHashMap<ChunkPosition, Rc<RefCell<Chunk<'a>>>>,
     pub transparent_chunks: FxHashMap<ChunkPosition, Rc<RefCell<Chunk<'a>>>>,
+    pub biomes: FxHashMap<(i32, i32), Arc<ChunkBiomes>>,
+    mesh_workers: MeshWorkers,
+    next_mesh_revision: u32,
+    lod: LodSettings,
+    //chunks the camera could see last frame, None draws everything
+    visible_chunks: Option<FxHashSet<ChunkPosition>>,
 }
 impl<'a> World<'a> {
-    pub fn new(block_registry: &'a BlockRegistry) -> Self {
+    pub fn new(block_registry: &'a Arc<BlockRegistry>, mesh_settings: MeshSettings) -> Self {
         World {
+            mesh_workers: MeshWorkers::new(block_registry.clone(), mesh_settings),
+            lod: mesh_settings.lod,
+            next_mesh_revision: 0,
+            visible_chunks: None,
             chunks: IndexMap::default(),
             block_registry,
-            light_updates: BTreeSet::new(),
+            light_removals: VecDeque::new(),
+            light_spreads: VecDeque::new(),
             chunk_mesh_updates: FxHashSet::default(),
             solid_chunks: FxHashMap::default(),
             transparent_chunks: FxHashMap::default(),
+            biomes: FxHashMap::default(),
         }
     }
     pub fn load_chunk(
@@ -1466,50 +1155,54 @@ impl<'a> World<'a> {
                 blocks,
                 self,
             )));
-            self.chunks.insert(position, chunk.clone());
+            self.chunks.insert(position, chunk);
             self.chunk_mesh_updates.insert(position);
-            for face in Face::all() {
-                let offset = face.get_offset();
-                let neighbor = self
-                    .chunks
-                    .get(&position.add(offset.x, offset.y, offset.z))
-                    .map(|chunk| chunk.clone());
-                {
-                    chunk
-                        .borrow_mut()
-                        .set_neighbor_by_face(&face, neighbor.clone());
-                }
-                {
-                    if let Some(neighbor) = neighbor {
-                        let mut neighbor = neighbor.borrow_mut();
-                        neighbor.set_neighbor_by_face(&face.opposite(), Some(chunk.clone()));
-                        neighbor.schedule_mesh_rebuild(self);
-                    }
-                }
-            }
-            self.light_updates.insert(BlockPosition {
-                x: position.x * 16 + 8,
-                y: position.y * 16 + 15,
-                z: position.z * 16 + 8,
-            });
+            self.schedule_neighbor_meshes(position);
+            self.seed_light(position);
         }
         self.chunks.get_mut(&position).unwrap().borrow_mut()
     }
     pub fn unload_chunk(&mut self, position: ChunkPosition) {
-        for face in Face::all() {
-            let offset = face.get_offset();
-            let neighbor = self.chunks.get(&position.add(offset.x, offset.y, offset.z));
-            {
-                if let Some(neighbor) = neighbor {
-                    neighbor
-                        .borrow_mut()
-                        .set_neighbor_by_face(&face.opposite(), None);
-                }
-            }
+        if self.chunks.swap_remove(&position).is_none() {
+            return;
         }
-        self.chunks.remove(&position);
         self.solid_chunks.remove(&position);
         self.transparent_chunks.remove(&position);
+        self.schedule_neighbor_meshes(position);
+        //the chunk below is the top of its column now and sees the sky again
+        let below = position.add(0, -1, 0);
+        if self.chunks.contains_key(&below) {
+            self.spread_from_top(below);
+        }
+        if !self
+            .chunks
+            .keys()
+            .any(|chunk| chunk.x == position.x && chunk.z == position.z)
+        {
+            self.biomes.remove(&(position.x, position.z));
+        }
+    }
+    //the edges of the neighbors were meshed against what was or wasn't there
+    fn schedule_neighbor_meshes(&mut self, position: ChunkPosition) {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbor = position.add(x, y, z);
+                    if neighbor != position && self.chunks.contains_key(&neighbor) {
+                        self.chunk_mesh_updates.insert(neighbor);
+                    }
+                }
+            }
+        }
+    }
+    //biomes are per column, so they stay until the last chunk of the column is gone
+    pub fn set_biomes(&mut self, x: i32, z: i32, biomes: ChunkBiomes) {
+        self.biomes.insert((x, z), Arc::new(biomes));
+        for position in self.chunks.keys() {
+            if position.x == x && position.z == z {
+                self.chunk_mesh_updates.insert(*position);
+            }
+        }
     }
     pub fn get_chunk(&self, position: ChunkPosition) -> Option<Ref<'_, Chunk<'a>>> {
         match self.chunks.get(&position) {
@@ -1531,37 +1224,8 @@ impl<'a> World<'a> {
     pub fn set_block(&mut self, position: BlockPosition, id: u32) -> Result<(), ()> {
         let chunk_position = position.to_chunk_pos();
         let offset = position.chunk_offset();
-        if offset.0 == 0 {
-            if let Some(chunk) = self.get_chunk_clone(chunk_position.add(-1, 0, 0)) {
-                chunk.borrow().schedule_mesh_rebuild(self);
-            }
-        }
-        if offset.0 == 15 {
-            if let Some(chunk) = self.get_chunk_clone(chunk_position.add(1, 0, 0)) {
-                chunk.borrow().schedule_mesh_rebuild(self);
-            }
-        }
-        if offset.1 == 0 {
-            if let Some(chunk) = self.get_chunk_clone(chunk_position.add(0, -1, 0)) {
-                chunk.borrow().schedule_mesh_rebuild(self);
-            }
-        }
-        if offset.1 == 15 {
-            if let Some(chunk) = self.get_chunk_clone(chunk_position.add(0, 1, 0)) {
-                chunk.borrow().schedule_mesh_rebuild(self);
-            }
-        }
-        if offset.2 == 0 {
-            if let Some(chunk) = self.get_chunk_clone(chunk_position.add(0, 0, -1)) {
-                chunk.borrow().schedule_mesh_rebuild(self);
-            }
-        }
-        if offset.2 == 15 {
-            if let Some(chunk) = self.get_chunk_clone(chunk_position.add(0, 0, 1)) {
-                chunk.borrow().schedule_mesh_rebuild(self);
-            }
-        }
-        self.light_updates.insert(position);
+        self.schedule_border_meshes(position);
+        self.block_light_changed(position);
         match self.get_chunk_clone(chunk_position) {
             Some(chunk) => {
                 chunk
@@ -1589,93 +1253,398 @@ impl<'a> World<'a> {
     pub fn set_light(&mut self, position: BlockPosition, light: (u8, u8, u8)) {
         if let Some(mut chunk) = self.get_mut_chunk(position.to_chunk_pos()) {
             let offset = position.chunk_offset();
-            let light = light.0 as u16 | ((light.1 as u16) << 4) | ((light.2 as u16) << 8);
-            //| ((light.3 as u16) << 12);
-            chunk.light[offset.0 as usize][offset.1 as usize][offset.2 as usize] = light;
-            //chunk.modified = true;
-            //todo
+            let stored = chunk.get_packed_light(offset.0, offset.1, offset.2);
+            chunk.set_packed_light(
+                offset.0,
+                offset.1,
+                offset.2,
+                (stored & (15 << 12))
+                    | light.0 as u16
+                    | ((light.1 as u16) << 4)
+                    | ((light.2 as u16) << 8),
+            );
+        } else {
+            return;
         }
+        self.light_changed(position);
     }
-    pub fn loaded(&self, position: ChunkPosition) -> bool {
-        self.get_chunk(position).is_some()
+    pub fn get_sky_light(&self, position: BlockPosition) -> Option<u8> {
+        self.get_chunk(position.to_chunk_pos()).map(|chunk| {
+            let offset = position.chunk_offset();
+            chunk.get_sky_light(offset.0, offset.1, offset.2)
+        })
     }
-    pub fn update_lights(&mut self) -> u32 {
-        self.light_updates.clear();
-        let updates = self.light_updates.len() as u32;
-        while let Some(light_update) = self.light_updates.pop_last() {
-            if self.get_block(light_update).map_or(false, |b| {
-                !self.block_registry.get_block(b).is_light_blocking()
-            }) {
-                let current_light = self.get_light(light_update).unwrap();
-                let mut biggest_light: (u8, u8, u8) = (
-                    current_light.0 + 1,
-                    current_light.1 + 1,
-                    current_light.2 + 1,
-                );
-                for face in Face::all() {
-                    let face_offset = face.get_offset();
-                    let block_pos = BlockPosition {
-                        x: light_update.x + face_offset.x,
-                        y: light_update.y + face_offset.y,
-                        z: light_update.z + face_offset.z,
-                    };
-                    let block_light = self
-                        .get_block(block_pos)
-                        .map_or((0, 0, 0), |b| self.block_registry.get_block(b).light);
-                    let light = self.get_light(block_pos).unwrap_or((0, 0, 0));
-
-                    biggest_light.0 = biggest_light.0.max(light.0).max(block_light.0);
-                    biggest_light.1 = biggest_light.1.max(light.1).max(block_light.1);
-                    biggest_light.2 = biggest_light.2.max(light.2).max(block_light.2);
+    pub fn set_sky_light(&mut self, position: BlockPosition, sky_light: u8) {
+        if let Some(mut chunk) = self.get_mut_chunk(position.to_chunk_pos()) {
+            let offset = position.chunk_offset();
+            let stored = chunk.get_packed_light(offset.0, offset.1, offset.2);
+            chunk.set_packed_light(
+                offset.0,
+                offset.1,
+                offset.2,
+                (stored & !(15 << 12)) | ((sky_light as u16) << 12),
+            );
+        } else {
+            return;
+        }
+        self.light_changed(position);
+    }
+    fn light_changed(&mut self, position: BlockPosition) {
+        self.schedule_border_meshes(position);
+    }
+    //meshes read the blocks around their faces and smooth lighting reads the edges and corners
+    //too, so a change on the border of a chunk touches every chunk next to that border
+    fn schedule_border_meshes(&mut self, position: BlockPosition) {
+        let chunk_position = position.to_chunk_pos();
+        let offset = position.chunk_offset();
+        let border = |offset: u8| match offset {
+            0 => -1..=0,
+            15 => 0..=1,
+            _ => 0..=0,
+        };
+        for x in border(offset.0) {
+            for y in border(offset.1) {
+                for z in border(offset.2) {
+                    let chunk = chunk_position.add(x, y, z);
+                    if self.chunks.contains_key(&chunk) {
+                        self.chunk_mesh_updates.insert(chunk);
+                    }
                 }
-                let new_light = (
-                    (biggest_light.0 - 1).max(0),
-                    (biggest_light.1 - 1).max(0),
-                    (biggest_light.2 - 1).max(0),
-                );
-                if new_light != current_light {
-                    self.set_light(light_update, new_light);
-                    for face in Face::all() {
-                        let face_offset = face.get_offset();
-                        let block_pos = BlockPosition {
-                            x: light_update.x + face_offset.x,
-                            y: light_update.y + face_offset.y,
-                            z: light_update.z + face_offset.z,
-                        };
-                        self.light_updates.insert(block_pos);
+            }
+        }
+    }
+    fn is_light_blocking_at(&self, position: BlockPosition) -> bool {
+        self.get_block(position).map_or(true, |block| {
+            self.block_registry.get_block(block).is_light_blocking()
+        })
+    }
+    //nothing is known above the highest loaded chunk of a column, so its top layer sees the sky
+    fn is_open_sky(&self, position: BlockPosition) -> bool {
+        position.chunk_offset().1 == 15
+            && !self
+                .chunks
+                .contains_key(&position.to_chunk_pos().add(0, 1, 0))
+    }
+    //r, g, b and sky, in the order of their nibbles
+    fn get_light_channels(&self, position: BlockPosition) -> Option<[u8; 4]> {
+        self.get_chunk(position.to_chunk_pos()).map(|chunk| {
+            let offset = position.chunk_offset();
+            let light = chunk.get_packed_light(offset.0, offset.1, offset.2);
+            [0, 4, 8, 12].map(|shift| ((light >> shift) & 15) as u8)
+        })
+    }
+    fn set_light_channels(&mut self, position: BlockPosition, light: [u8; 4]) {
+        if self.get_light_channels(position) == Some(light) {
+            return;
+        }
+        if let Some(mut chunk) = self.get_mut_chunk(position.to_chunk_pos()) {
+            let offset = position.chunk_offset();
+            chunk.set_packed_light(
+                offset.0,
+                offset.1,
+                offset.2,
+                light[0] as u16
+                    | ((light[1] as u16) << 4)
+                    | ((light[2] as u16) << 8)
+                    | ((light[3] as u16) << 12),
+            );
+        }
+        self.light_changed(position);
+    }
+    //what a block gives off by itself, open sky counts as a source of sky light
+    fn light_sources(&self, position: BlockPosition) -> [u8; 4] {
+        let Some(block) = self.get_block(position) else {
+            return [0; 4];
+        };
+        let block = self.block_registry.get_block(block);
+        let sky = if !block.is_light_blocking() && self.is_open_sky(position) {
+            15
+        } else {
+            0
+        };
+        [block.light.0, block.light.1, block.light.2, sky]
+    }
+    fn seed_light(&mut self, position: ChunkPosition) {
+        //the chunk below was lit as open sky until now
+        let below = position.add(0, -1, 0);
+        if self.chunks.contains_key(&below) {
+            for x in 0..16 {
+                for z in 0..16 {
+                    let block = BlockPosition {
+                        x: below.x * 16 + x,
+                        y: below.y * 16 + 15,
+                        z: below.z * 16 + z,
+                    };
+                    let light = self.get_sky_light(block).unwrap();
+                    if light > 0 {
+                        self.set_sky_light(block, 0);
+                        self.light_removals.push_back((block, 3, light));
                     }
                 }
             }
         }
+        //light flows in from the neighbors and down from the sky, emitters were queued by the chunk
+        for face in Face::all() {
+            if !self.chunks.contains_key(&position.with_offset(face)) {
+                continue;
+            }
+            let offset = face.get_offset();
+            for i in 0..16 {
+                for j in 0..16 {
+                    let in_chunk = match face {
+                        Face::Left | Face::Right => (if offset.x < 0 { 0 } else { 15 }, i, j),
+                        Face::Up | Face::Down => (i, if offset.y < 0 { 0 } else { 15 }, j),
+                        Face::Front | Face::Back => (i, j, if offset.z < 0 { 0 } else { 15 }),
+                    };
+                    self.light_spreads.push_back(BlockPosition {
+                        x: position.x * 16 + in_chunk.0 + offset.x,
+                        y: position.y * 16 + in_chunk.1 + offset.y,
+                        z: position.z * 16 + in_chunk.2 + offset.z,
+                    });
+                }
+            }
+        }
+        self.spread_from_top(position);
+    }
+    fn spread_from_top(&mut self, position: ChunkPosition) {
+        for x in 0..16 {
+            for z in 0..16 {
+                self.light_spreads.push_back(BlockPosition {
+                    x: position.x * 16 + x,
+                    y: position.y * 16 + 15,
+                    z: position.z * 16 + z,
+                });
+            }
+        }
+    }
+    //called before the block is replaced, the old block might have been a light source
+    fn block_light_changed(&mut self, position: BlockPosition) {
+        let Some(light) = self.get_light_channels(position) else {
+            return;
+        };
+        let sources = self.light_sources(position);
+        for channel in 0..4 {
+            let old = light[channel].max(sources[channel]);
+            if old > 0 {
+                self.light_removals.push_back((position, channel, old));
+            }
+        }
+        self.set_light_channels(position, [0; 4]);
+        self.light_spreads.push_back(position);
+        for face in Face::all() {
+            self.light_spreads.push_back(position + face.get_offset());
+        }
+    }
+    //every removal runs before any spread, so stopping at the budget and continuing next frame
+    //ends in the same light as doing it all at once
+    pub fn update_lights(&mut self) -> u32 {
+        self.update_lights_with_budget(LIGHT_UPDATES_PER_FRAME)
+    }
+    fn update_lights_with_budget(&mut self, budget: u32) -> u32 {
+        let mut updates = 0;
+        while updates < budget {
+            if let Some((position, channel, light)) = self.light_removals.pop_front() {
+                self.remove_light(position, channel, light);
+            } else if let Some(position) = self.light_spreads.pop_front() {
+                self.spread_light(position);
+            } else {
+                break;
+            }
+            updates += 1;
+        }
         updates
     }
+    //darkens what this light could have lit, brighter neighbors have another source and refill it
+    fn remove_light(&mut self, position: BlockPosition, channel: usize, light: u8) {
+        for face in Face::all() {
+            let neighbor = position + face.get_offset();
+            let Some(mut neighbor_light) = self.get_light_channels(neighbor) else {
+                continue;
+            };
+            let value = neighbor_light[channel];
+            let lit_from_here = value > 0
+                && (value < light
+                    || (channel == 3 && *face == Face::Down && light == 15 && value == 15));
+            if lit_from_here {
+                neighbor_light[channel] = 0;
+                self.set_light_channels(neighbor, neighbor_light);
+                self.light_removals.push_back((neighbor, channel, value));
+            }
+            if (value > 0 && !lit_from_here) || self.light_sources(neighbor)[channel] > 0 {
+                self.light_spreads.push_back(neighbor);
+            }
+        }
+    }
+    //sky light falls straight down without loss, everything else loses a level per block
+    fn spread_light(&mut self, position: BlockPosition) {
+        let Some(mut light) = self.get_light_channels(position) else {
+            return;
+        };
+        let sources = self.light_sources(position);
+        if self.is_light_blocking_at(position) {
+            //opaque emitters only pass on their own light
+            light = sources;
+        } else {
+            let lit = [0, 1, 2, 3].map(|channel| light[channel].max(sources[channel]));
+            if lit != light {
+                light = lit;
+                self.set_light_channels(position, light);
+            }
+        }
+        for face in Face::all() {
+            let neighbor = position + face.get_offset();
+            if self.is_light_blocking_at(neighbor) {
+                continue;
+            }
+            let Some(mut neighbor_light) = self.get_light_channels(neighbor) else {
+                continue;
+            };
+            let mut changed = false;
+            for channel in 0..4 {
+                let spread = if channel == 3 && *face == Face::Down && light[3] == 15 {
+                    15
+                } else {
+                    light[channel].saturating_sub(1)
+                };
+                if spread > neighbor_light[channel] {
+                    neighbor_light[channel] = spread;
+                    changed = true;
+                }
+            }
+            if changed {
+                self.set_light_channels(neighbor, neighbor_light);
+                self.light_spreads.push_back(neighbor);
+            }
+        }
+    }
+    //missing neighbors are filled in by the mesh settings and remeshed once they arrive
+    fn mesh_snapshot(&mut self, position: ChunkPosition) -> Option<MeshSnapshot> {
+        let mut neighbors: [Option<Arc<ChunkData>>; 27] = Default::default();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if (x, y, z) != (0, 0, 0) {
+                        neighbors[MeshSnapshot::neighbor_index(x, y, z)] = self
+                            .chunks
+                            .get(&position.add(x, y, z))
+                            .map(|neighbor| neighbor.borrow().data.clone());
+                    }
+                }
+            }
+        }
+        let mut chunk = self.chunks.get(&position)?.borrow_mut();
+        self.next_mesh_revision = self.next_mesh_revision.wrapping_add(1);
+        chunk.mesh_revision = self.next_mesh_revision;
+        chunk.mesh_in_flight = true;
+        Some(MeshSnapshot {
+            position,
+            revision: chunk.mesh_revision,
+            chunk: chunk.data.clone(),
+            neighbors,
+            biomes: self.biomes.get(&(position.x, position.z)).cloned(),
+            lod: chunk.lod,
+        })
+    }
+    pub fn loaded(&self, position: ChunkPosition) -> bool {
+        self.get_chunk(position).is_some()
+    }
     pub fn render(
         &mut self,
         shader: &glwrappers::Shader,
         time: f32,
-        player_position: ChunkPosition,
-    ) -> (i32, i32, i32, i32, i32) {
+        eye: Position,
+        frustum: &Frustum,
+    ) -> (i32, i32, i32, i32, i32, i32, i32) {
+        //before meshing, so new chunks don't get a dark mesh first
+        let light_updates = self.update_lights() as i32;
+        let player = Vec3::new(eye.x, eye.y, eye.z);
+        for chunk in self.chunks.values() {
+            let mut chunk = chunk.borrow_mut();
+            let lod = self.lod.select(chunk.lod, (chunk.center() - player).mag());
+            if lod != chunk.lod {
+                chunk.lod = lod;
+                self.chunk_mesh_updates.insert(chunk.position);
+            }
+        }
+        //the walk starts from the eye, the feet can be in the chunk below it
+        let player_position = eye.to_chunk_pos();
+        //chunks with a job in flight stay dirty until it lands, so light spreading over several
+        //frames doesnt queue a rebuild every frame
         let mesh_updates: Vec<_> = self
             .chunk_mesh_updates
-            .extract_if(|_| true)
-            .take(200)
-            .collect(); //todo: optimize
+            .iter()
+            .copied()
+            .filter(|position| {
+                self.chunks
+                    .get(position)
+                    .map_or(true, |chunk| !chunk.borrow().mesh_in_flight)
+            })
+            .collect();
         for mesh_update in mesh_updates {
-            if let Some(chunk) = self.get_chunk_clone(mesh_update) {
-                let cloned_chunk = chunk.clone();
-                chunk.borrow_mut().rebuild_chunk_mesh(cloned_chunk, self);
+            self.chunk_mesh_updates.remove(&mesh_update);
+            if let Some(snapshot) = self.mesh_snapshot(mesh_update) {
+                self.mesh_workers.submit(snapshot);
+            }
+        }
+        for mesh in self.mesh_workers.finished() {
+            let Some(chunk) = self.get_chunk_clone(mesh.position) else {
+                continue;
+            };
+            let mut chunk = chunk.borrow_mut();
+            //built for an earlier copy of the chunk that was unloaded since
+            if chunk.mesh_revision != mesh.revision {
+                continue;
+            }
+            chunk.mesh_in_flight = false;
+            let (solid, transparent) = chunk.upload_mesh(mesh);
+            let this = self.chunks.get(&chunk.position).unwrap().clone();
+            if solid {
+                self.solid_chunks.insert(chunk.position, this.clone());
+            } else {
+                self.solid_chunks.remove(&chunk.position);
+            }
+            if transparent {
+                self.transparent_chunks.insert(chunk.position, this);
+            } else {
+                self.transparent_chunks.remove(&chunk.position);
             }
         }
         unsafe {
             ogl33::glEnable(GL_CULL_FACE);
         }
-        let light_updates = self.update_lights() as i32;
-        let mut rendered_chunks_stat = (0, 0, 0, self.chunks.len() as i32, light_updates);
+        //the camera chunk itself might not be loaded yet, then there is nothing to walk from
+        self.visible_chunks = self.chunks.contains_key(&player_position).then(|| {
+            visibility::visible_chunks(
+                player_position,
+                |position| {
+                    self.chunks
+                        .get(&position)
+                        .map(|chunk| chunk.borrow().connections)
+                },
+                |position| {
+                    self.chunks
+                        .get(&position)
+                        .map_or(false, |chunk| chunk.borrow().in_frustum(frustum))
+                },
+            )
+        });
+        let mut rendered_chunks_stat = (
+            0,
+            0,
+            0,
+            self.chunks.len() as i32,
+            light_updates,
+            0,
+            (self.memory_usage() / 1024) as i32,
+        );
         shader.set_uniform_float(shader.get_uniform_location("time\0").unwrap(), time);
         for chunk in self.solid_chunks.values() {
-            chunk
-                .borrow_mut()
-                .render(shader, true, &mut rendered_chunks_stat);
+            let mut chunk = chunk.borrow_mut();
+            if !chunk.in_frustum(frustum) || !self.is_visible(chunk.position) {
+                rendered_chunks_stat.5 += 1;
+                continue;
+            }
+            chunk.render(shader, true, &mut rendered_chunks_stat);
         }
         /*for chunk in self.chunks.values() {
             let mut borrowed_chunk = chunk.borrow_mut();
@@ -1686,29 +1655,50 @@ impl<'a> World<'a> {
                 &mut rendered_chunks_stat,
             );
         }*/
-        /*rendered_chunks.sort_by(|a, b| {
-            let a = a.borrow();
-            let b = b.borrow();
-            a.position
-                .distance_squared(&player_position)
-                .cmp(&b.position.distance_squared(&player_position))
-        });*/
         rendered_chunks_stat
     }
+    //bytes of block and light storage of all loaded chunks
+    pub fn memory_usage(&self) -> usize {
+        self.chunks
+            .values()
+            .map(|chunk| chunk.borrow().memory_usage())
+            .sum()
+    }
+    fn is_visible(&self, position: ChunkPosition) -> bool {
+        self.visible_chunks
+            .as_ref()
+            .map_or(true, |visible| visible.contains(&position))
+    }
     pub fn render_transparent(
         &self,
         shader: &glwrappers::Shader,
-        rendered_chunk_stats: &mut (i32, i32, i32, i32, i32),
+        frustum: &Frustum,
+        camera: Vec3,
+        rendered_chunk_stats: &mut (i32, i32, i32, i32, i32, i32, i32),
     ) {
         unsafe {
             ogl33::glBlendFunc(ogl33::GL_SRC_ALPHA, ogl33::GL_ONE_MINUS_SRC_ALPHA);
             ogl33::glEnable(ogl33::GL_BLEND);
             ogl33::glDisable(ogl33::GL_CULL_FACE);
         }
+        let mut chunks = Vec::new();
         for chunk in self.transparent_chunks.values() {
-            chunk
-                .borrow()
-                .render_transparent(shader, rendered_chunk_stats);
+            let borrowed_chunk = chunk.borrow();
+            if !borrowed_chunk.in_frustum(frustum) || !self.is_visible(borrowed_chunk.position) {
+                //chunks with solid geometry were already counted by the solid pass
+                if !self.solid_chunks.contains_key(&borrowed_chunk.position) {
+                    rendered_chunk_stats.5 += 1;
+                }
+                continue;
+            }
+            chunks.push(((borrowed_chunk.center() - camera).mag_sq(), chunk));
+        }
+        //back to front, so closer glass and water blend over what is behind them
+        chunks.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, chunk) in chunks {
+            let mut chunk = chunk.borrow_mut();
+            chunk.sort_transparent(camera);
+            chunk.render_transparent(shader, rendered_chunk_stats);
         }
         unsafe {
             ogl33::glDisable(ogl33::GL_BLEND);
@@ -1887,12 +1877,20 @@ pub struct Particle {
     destroyed: bool,
 }
 
+//ogg sounds under this directory are kept compressed and decoded while playing
+const STREAMED_SOUND_PREFIX: &str = "music/";
+//how many buffers of how many frames are kept queued for a streamed sound
+const STREAM_BUFFER_COUNT: usize = 4;
+const STREAM_BUFFER_FRAMES: usize = 16384;
+
 pub struct SoundManager {
     alto: Alto,
     device: alto::OutputDevice,
     context: alto::Context,
     buffers: HashMap<String, Arc<alto::Buffer>>,
+    streamed: HashMap<String, Arc<[u8]>>,
     sounds: Vec<alto::StaticSource>,
+    streams: Vec<SoundStream>,
 }
 impl SoundManager {
     pub fn new() -> Self {
@@ -1905,18 +1903,30 @@ impl SoundManager {
             device,
             context,
             buffers: HashMap::new(),
+            streamed: HashMap::new(),
             sounds: Vec::new(),
+            streams: Vec::new(),
         }
     }
     pub fn load(&mut self, name: String, data: Vec<u8>) {
-        let reader = hound::WavReader::new(data.as_slice()).unwrap();
-        let frequency = reader.spec().sample_rate as i32;
-        let samples: Vec<i16> = reader.into_samples().map(|s| s.unwrap()).collect();
-        let buffer = self
-            .context
-            .new_buffer::<alto::Mono<i16>, &[i16]>(samples.as_slice(), frequency)
-            .unwrap();
-        self.buffers.insert(name, Arc::new(buffer));
+        //only ogg vorbis is streamed, other music is decoded up front like any other sound
+        if name.starts_with(STREAMED_SOUND_PREFIX) && data.starts_with(b"OggS") {
+            self.streamed.insert(name, data.into());
+            return;
+        }
+        let decoded = if data.starts_with(b"OggS") {
+            decode_ogg(data.as_slice())
+        } else {
+            decode_wav(data.as_slice())
+        };
+        match decoded.and_then(|(samples, channels, frequency)| {
+            create_sound_buffer(&self.context, samples.as_slice(), channels, frequency)
+        }) {
+            Ok(buffer) => {
+                self.buffers.insert(name, Arc::new(buffer));
+            }
+            Err(error) => println!("couldnt load sound {}: {}", name, error),
+        }
     }
     pub fn play_sound(
         &mut self,
@@ -1926,17 +1936,39 @@ impl SoundManager {
         pitch: f32,
         relative: bool,
     ) {
+        if let Some(data) = self.streamed.get(&name) {
+            match SoundStream::new(&self.context, data.clone()) {
+                Ok(mut stream) => {
+                    set_source_properties(
+                        &mut stream.source,
+                        stream.channels,
+                        position,
+                        gain,
+                        pitch,
+                        relative,
+                    );
+                    stream.source.play();
+                    self.streams.push(stream);
+                }
+                Err(error) => println!("couldnt stream sound {}: {}", name, error),
+            }
+            return;
+        }
+        let Some(buffer) = self.buffers.get(&name) else {
+            println!("sound {} not found", name);
+            return;
+        };
         let mut source = self.context.new_static_source().unwrap();
-        source
-            .set_buffer(self.buffers.get(&name).unwrap().clone())
-            .unwrap();
-        source
-            .set_position([position.x, position.y, position.z])
-            .unwrap();
-        source.set_gain(gain).unwrap();
-        source.set_pitch(pitch).unwrap();
+        source.set_buffer(buffer.clone()).unwrap();
+        set_source_properties(
+            &mut source,
+            buffer.channels() as u16,
+            position,
+            gain,
+            pitch,
+            relative,
+        );
         source.set_looping(false);
-        source.set_relative(relative);
         source.play();
         self.sounds.push(source);
     }
@@ -1954,5 +1986,342 @@ impl SoundManager {
         self.sounds
             .extract_if(|source| source.state() != alto::SourceState::Playing)
             .count();
+        self.streams.retain_mut(|stream| stream.tick());
+    }
+}
+fn set_source_properties<S: Source>(
+    source: &mut S,
+    channels: u16,
+    position: Position,
+    gain: f32,
+    pitch: f32,
+    relative: bool,
+) {
+    //openal only spatializes mono buffers, so stereo sounds are always played at the listener
+    if channels == 1 {
+        source
+            .set_position([position.x, position.y, position.z])
+            .unwrap();
+        source.set_relative(relative);
+    } else {
+        source.set_position([0., 0., 0.]).unwrap();
+        source.set_relative(true);
+    }
+    source.set_gain(gain).unwrap();
+    source.set_pitch(pitch).unwrap();
+}
+fn create_sound_buffer(
+    context: &alto::Context,
+    samples: &[i16],
+    channels: u16,
+    frequency: i32,
+) -> Result<alto::Buffer, String> {
+    match channels {
+        1 => context.new_buffer::<alto::Mono<i16>, &[i16]>(samples, frequency),
+        2 => context.new_buffer::<alto::Stereo<i16>, &[i16]>(samples, frequency),
+        _ => return Err(format!("unsupported channel count {}", channels)),
+    }
+    .map_err(|error| error.to_string())
+}
+//returns interleaved samples, channel count and sample rate
+pub fn decode_wav(data: &[u8]) -> Result<(Vec<i16>, u16, i32), String> {
+    let reader = hound::WavReader::new(data).map_err(|error| error.to_string())?;
+    let spec = reader.spec();
+    let samples = match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Int, 16) => reader
+            .into_samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| error.to_string())?,
+        (hound::SampleFormat::Int, bits) if bits <= 32 => reader
+            .into_samples::<i32>()
+            .map(|sample| {
+                sample.map(|sample| {
+                    if bits > 16 {
+                        (sample >> (bits - 16)) as i16
+                    } else {
+                        (sample << (16 - bits)) as i16
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| error.to_string())?,
+        (hound::SampleFormat::Float, 32) => reader
+            .into_samples::<f32>()
+            .map(|sample| sample.map(|sample| (sample.clamp(-1., 1.) * i16::MAX as f32) as i16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| error.to_string())?,
+        (format, bits) => return Err(format!("unsupported wav format {:?} {}", format, bits)),
+    };
+    Ok((samples, spec.channels, spec.sample_rate as i32))
+}
+pub fn decode_ogg(data: &[u8]) -> Result<(Vec<i16>, u16, i32), String> {
+    let mut reader = lewton::inside_ogg::OggStreamReader::new(std::io::Cursor::new(data))
+        .map_err(|error| error.to_string())?;
+    let mut samples = Vec::new();
+    while let Some(packet) = reader
+        .read_dec_packet_itl()
+        .map_err(|error| error.to_string())?
+    {
+        samples.extend(packet);
+    }
+    Ok((
+        samples,
+        reader.ident_hdr.audio_channels as u16,
+        reader.ident_hdr.audio_sample_rate as i32,
+    ))
+}
+struct SoundStream {
+    source: alto::StreamingSource,
+    reader: lewton::inside_ogg::OggStreamReader<std::io::Cursor<Arc<[u8]>>>,
+    channels: u16,
+    frequency: i32,
+    finished: bool,
+}
+impl SoundStream {
+    pub fn new(context: &alto::Context, data: Arc<[u8]>) -> Result<Self, String> {
+        let reader = lewton::inside_ogg::OggStreamReader::new(std::io::Cursor::new(data))
+            .map_err(|error| error.to_string())?;
+        let mut stream = SoundStream {
+            source: context
+                .new_streaming_source()
+                .map_err(|error| error.to_string())?,
+            channels: reader.ident_hdr.audio_channels as u16,
+            frequency: reader.ident_hdr.audio_sample_rate as i32,
+            reader,
+            finished: false,
+        };
+        for _ in 0..STREAM_BUFFER_COUNT {
+            let Some(samples) = stream.decode_chunk() else {
+                break;
+            };
+            let buffer = create_sound_buffer(
+                context,
+                samples.as_slice(),
+                stream.channels,
+                stream.frequency,
+            )?;
+            stream
+                .source
+                .queue_buffer(buffer)
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(stream)
+    }
+    fn decode_chunk(&mut self) -> Option<Vec<i16>> {
+        if self.finished {
+            return None;
+        }
+        let wanted = STREAM_BUFFER_FRAMES * self.channels as usize;
+        let mut samples = Vec::with_capacity(wanted);
+        while samples.len() < wanted {
+            match self.reader.read_dec_packet_itl() {
+                Ok(Some(packet)) => samples.extend(packet),
+                Ok(None) | Err(_) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        if samples.is_empty() {
+            None
+        } else {
+            Some(samples)
+        }
+    }
+    //refills processed buffers, returns false once everything was played
+    pub fn tick(&mut self) -> bool {
+        for _ in 0..self.source.buffers_processed() {
+            let Ok(mut buffer) = self.source.unqueue_buffer() else {
+                break;
+            };
+            if let Some(samples) = self.decode_chunk() {
+                let result = match self.channels {
+                    1 => buffer
+                        .set_data::<alto::Mono<i16>, &[i16]>(samples.as_slice(), self.frequency),
+                    _ => buffer
+                        .set_data::<alto::Stereo<i16>, &[i16]>(samples.as_slice(), self.frequency),
+                };
+                if result.is_ok() {
+                    self.source.queue_buffer(buffer).unwrap();
+                }
+            }
+        }
+        if self.source.buffers_queued() == 0 {
+            return false;
+        }
+        //the source stops by itself when decoding couldnt keep up
+        if self.source.state() != alto::SourceState::Playing {
+            if self.finished && self.source.buffers_processed() == self.source.buffers_queued() {
+                return false;
+            }
+            self.source.play();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meshing::MissingNeighbors;
+
+    const TORCH: BlockPosition = BlockPosition { x: 15, y: 8, z: 8 };
+
+    //two air chunks next to each other along x, roofed with stone so no sky light gets in
+    fn world(registry: &Arc<BlockRegistry>) -> World<'_> {
+        let mut world = World::new(
+            registry,
+            MeshSettings {
+                smooth_lighting: false,
+                missing_neighbors: MissingNeighbors::Air,
+                lod: LodSettings::default(),
+            },
+        );
+        for x in 0..2 {
+            world.load_chunk(ChunkPosition { x, y: 0, z: 0 }, [[[0; 16]; 16]; 16]);
+            world.load_chunk(
+                ChunkPosition { x, y: 1, z: 0 },
+                [[[BlockRegistry::STONE; 16]; 16]; 16],
+            );
+        }
+        drain(&mut world);
+        world
+    }
+    fn drain(world: &mut World) {
+        world.update_lights_with_budget(u32::MAX);
+    }
+    fn frames(world: &mut World, count: usize) {
+        for _ in 0..count {
+            world.update_lights_with_budget(50);
+        }
+    }
+    fn light_at(world: &World, x: i32) -> (u8, u8, u8) {
+        world
+            .get_light(BlockPosition {
+                x,
+                y: TORCH.y,
+                z: TORCH.z,
+            })
+            .unwrap()
+    }
+    //packed light of every block in the air chunks
+    fn all_light(world: &World) -> Vec<u16> {
+        let mut light = Vec::new();
+        for chunk_x in 0..2 {
+            let chunk = world
+                .get_chunk(ChunkPosition {
+                    x: chunk_x,
+                    y: 0,
+                    z: 0,
+                })
+                .unwrap();
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        light.push(chunk.get_packed_light(x, y, z));
+                    }
+                }
+            }
+        }
+        light
+    }
+
+    #[test]
+    fn torch_lights_neighbor_chunk() {
+        let registry = Arc::new(BlockRegistry::for_tests());
+        let mut world = world(&registry);
+        assert!(all_light(&world).iter().all(|light| *light == 0));
+        world.set_block(TORCH, BlockRegistry::TORCH).unwrap();
+        drain(&mut world);
+        assert_eq!(light_at(&world, 15), (14, 14, 14));
+        assert_eq!(light_at(&world, 16), (13, 13, 13));
+        assert_eq!(light_at(&world, 20), (9, 9, 9));
+        assert_eq!(light_at(&world, 28), (1, 1, 1));
+        assert_eq!(light_at(&world, 29), (0, 0, 0));
+        assert_eq!(light_at(&world, 2), (1, 1, 1));
+    }
+
+    #[test]
+    fn breaking_torch_darkens_both_chunks() {
+        let registry = Arc::new(BlockRegistry::for_tests());
+        let mut world = world(&registry);
+        world.set_block(TORCH, BlockRegistry::TORCH).unwrap();
+        drain(&mut world);
+        world.set_block(TORCH, 0).unwrap();
+        drain(&mut world);
+        assert!(all_light(&world).iter().all(|light| *light == 0));
+    }
+
+    #[test]
+    fn wall_keeps_torch_light_in_its_chunk() {
+        let registry = Arc::new(BlockRegistry::for_tests());
+        let mut world = world(&registry);
+        for y in 0..16 {
+            for z in 0..16 {
+                world
+                    .set_block(BlockPosition { x: 16, y, z }, BlockRegistry::STONE)
+                    .unwrap();
+            }
+        }
+        world.set_block(TORCH, BlockRegistry::TORCH).unwrap();
+        drain(&mut world);
+        assert_eq!(light_at(&world, 15), (14, 14, 14));
+        assert_eq!(light_at(&world, 16), (0, 0, 0));
+        assert_eq!(light_at(&world, 17), (0, 0, 0));
+    }
+
+    #[test]
+    fn budgeted_frames_match_draining_at_once() {
+        let registry = Arc::new(BlockRegistry::for_tests());
+        let second_torch = BlockPosition { x: 20, y: 4, z: 3 };
+        let mut at_once = world(&registry);
+        let mut budgeted = world(&registry);
+
+        at_once.set_block(TORCH, BlockRegistry::TORCH).unwrap();
+        at_once
+            .set_block(second_torch, BlockRegistry::TORCH)
+            .unwrap();
+        drain(&mut at_once);
+        budgeted.set_block(TORCH, BlockRegistry::TORCH).unwrap();
+        frames(&mut budgeted, 3);
+        budgeted
+            .set_block(second_torch, BlockRegistry::TORCH)
+            .unwrap();
+        while budgeted.update_lights_with_budget(50) > 0 {}
+        assert_eq!(all_light(&budgeted), all_light(&at_once));
+
+        //broken while its light is still spreading
+        at_once.set_block(TORCH, 0).unwrap();
+        drain(&mut at_once);
+        budgeted.set_block(TORCH, 0).unwrap();
+        frames(&mut budgeted, 2);
+        budgeted.set_block(TORCH, BlockRegistry::TORCH).unwrap();
+        frames(&mut budgeted, 2);
+        budgeted.set_block(TORCH, 0).unwrap();
+        while budgeted.update_lights_with_budget(50) > 0 {}
+        assert_eq!(all_light(&budgeted), all_light(&at_once));
+        assert_eq!(budgeted.get_light(second_torch), Some((14, 14, 14)));
+        assert_eq!(light_at(&budgeted, 20), (5, 5, 5));
+        assert_eq!(light_at(&budgeted, 15), (0, 0, 0));
+    }
+
+    #[test]
+    fn unloading_roof_lets_sky_in_and_loading_it_blocks_it_again() {
+        let registry = Arc::new(BlockRegistry::for_tests());
+        let mut world = world(&registry);
+        let roof = ChunkPosition { x: 0, y: 1, z: 0 };
+        let floor = BlockPosition { x: 3, y: 0, z: 3 };
+        assert_eq!(world.get_sky_light(floor), Some(0));
+        world.unload_chunk(roof);
+        drain(&mut world);
+        assert_eq!(world.get_sky_light(floor), Some(15));
+        //spreads sideways under the roof of the other chunk
+        assert_eq!(
+            world.get_sky_light(BlockPosition { x: 16, y: 0, z: 3 }),
+            Some(14)
+        );
+        world.load_chunk(roof, [[[BlockRegistry::STONE; 16]; 16]; 16]);
+        drain(&mut world);
+        assert!(all_light(&world).iter().all(|light| *light == 0));
     }
 }
@@ -8,7 +8,16 @@ pub struct Vertex {
     pub v: f32,
     pub render_data: u8,
     pub light: u16,
+    pub animation: u8,
+    pub tint: [u8; 3],
+    //atlas rect the uvs repeat in, used by merged faces
+    pub tile: [u16; 4],
+    //ambient occlusion, 0 is a fully occluded corner
+    pub ao: u8,
 }
+//uvs are plain atlas coordinates
+pub const NO_TILE: [u16; 4] = [0; 4];
+pub const NO_OCCLUSION: u8 = 3;
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
 pub struct ModelVertex {
@@ -24,7 +33,7 @@ unsafe impl bytemuck::Pod for Vertex {}
 unsafe impl bytemuck::Zeroable for ModelVertex {}
 unsafe impl bytemuck::Pod for ModelVertex {}
 pub type ColorVertex = [f32; 3 + 3];
-pub type GuiVertex = [f32; 2 + 2 + 4];
+pub type GuiVertex = [f32; 2 + 2 + 4 + 1];
 pub type BasicVertex = [f32; 3 + 2];
 pub type SkyVertex = [f32; 3 + 1];
 
@@ -134,6 +143,20 @@ impl Shader {
             ogl33::glUniform1f(uniform_location as i32, value);
         }
     }
+    pub fn set_uniform_vec4_array(&self, uniform_location: u32, values: &[[f32; 4]]) {
+        unsafe {
+            ogl33::glUniform4fv(
+                uniform_location as i32,
+                values.len() as i32,
+                values.as_ptr() as *const f32,
+            );
+        }
+    }
+    pub fn set_uniform_int(&self, uniform_location: u32, value: i32) {
+        unsafe {
+            ogl33::glUniform1i(uniform_location as i32, value);
+        }
+    }
     pub fn set_uniform_vec3(&self, uniform_location: u32, value: (i32, i32, i32)) {
         unsafe {
             ogl33::glUniform3i(uniform_location as i32, value.0, value.1, value.2);
@@ -216,6 +239,33 @@ impl Drop for Buffer {
         }
     }
 }
+const GL_TEXTURE_MAX_ANISOTROPY: ogl33::GLenum = 0x84FE;
+const GL_MAX_TEXTURE_MAX_ANISOTROPY: ogl33::GLenum = 0x84FF;
+
+pub fn has_extension(name: &str) -> bool {
+    unsafe {
+        let mut count = 0;
+        ogl33::glGetIntegerv(ogl33::GL_NUM_EXTENSIONS, &mut count);
+        (0..count).any(|i| {
+            let extension = ogl33::glGetStringi(ogl33::GL_EXTENSIONS, i as u32);
+            !extension.is_null()
+                && std::ffi::CStr::from_ptr(extension as *const std::ffi::c_char).to_bytes()
+                    == name.as_bytes()
+        })
+    }
+}
+fn max_anisotropy() -> Option<f32> {
+    if !has_extension("GL_EXT_texture_filter_anisotropic")
+        && !has_extension("GL_ARB_texture_filter_anisotropic")
+    {
+        return None;
+    }
+    let mut max = 0f32;
+    unsafe {
+        ogl33::glGetFloatv(GL_MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+    }
+    Some(max)
+}
 pub struct Texture {
     tex_id: u32,
 }
@@ -275,9 +325,151 @@ impl Texture {
             None
         }
     }
+    pub fn new_mipmapped(levels: Vec<(Vec<u8>, u32, u32)>, anisotropy: f32) -> Option<Texture> {
+        let mut tex_id = 0;
+        unsafe {
+            ogl33::glGenTextures(1, &mut tex_id);
+        }
+        if tex_id != 0 {
+            unsafe {
+                ogl33::glBindTexture(ogl33::GL_TEXTURE_2D, tex_id);
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_WRAP_S,
+                    ogl33::GL_REPEAT as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_WRAP_T,
+                    ogl33::GL_REPEAT as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_MIN_FILTER,
+                    if levels.len() > 1 {
+                        ogl33::GL_NEAREST_MIPMAP_LINEAR
+                    } else {
+                        ogl33::GL_NEAREST
+                    } as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_MAG_FILTER,
+                    ogl33::GL_NEAREST as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(ogl33::GL_TEXTURE_2D, ogl33::GL_TEXTURE_BASE_LEVEL, 0);
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_MAX_LEVEL,
+                    levels.len().saturating_sub(1) as ogl33::GLint,
+                );
+                if anisotropy > 1. {
+                    if let Some(max_anisotropy) = max_anisotropy() {
+                        ogl33::glTexParameterf(
+                            ogl33::GL_TEXTURE_2D,
+                            GL_TEXTURE_MAX_ANISOTROPY,
+                            anisotropy.min(max_anisotropy),
+                        );
+                    }
+                }
+                for (level, (bitmap, width, height)) in levels.iter().enumerate() {
+                    ogl33::glTexImage2D(
+                        ogl33::GL_TEXTURE_2D,
+                        level as i32,
+                        ogl33::GL_RGBA as ogl33::GLint,
+                        *width as i32,
+                        *height as i32,
+                        0,
+                        ogl33::GL_RGBA,
+                        ogl33::GL_UNSIGNED_BYTE,
+                        bitmap.as_ptr().cast(),
+                    );
+                }
+            }
+            Some(Texture { tex_id })
+        } else {
+            None
+        }
+    }
+    pub fn new_alpha(width: u32, height: u32) -> Option<Texture> {
+        let mut tex_id = 0;
+        unsafe {
+            ogl33::glGenTextures(1, &mut tex_id);
+        }
+        if tex_id != 0 {
+            unsafe {
+                ogl33::glBindTexture(ogl33::GL_TEXTURE_2D, tex_id);
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_WRAP_S,
+                    ogl33::GL_CLAMP_TO_EDGE as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_WRAP_T,
+                    ogl33::GL_CLAMP_TO_EDGE as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_MIN_FILTER,
+                    ogl33::GL_LINEAR as ogl33::GLint,
+                );
+                ogl33::glTexParameteri(
+                    ogl33::GL_TEXTURE_2D,
+                    ogl33::GL_TEXTURE_MAG_FILTER,
+                    ogl33::GL_LINEAR as ogl33::GLint,
+                );
+                let empty = vec![0u8; (width * height) as usize];
+                ogl33::glPixelStorei(ogl33::GL_UNPACK_ALIGNMENT, 1);
+                ogl33::glTexImage2D(
+                    ogl33::GL_TEXTURE_2D,
+                    0,
+                    ogl33::GL_R8 as ogl33::GLint,
+                    width as i32,
+                    height as i32,
+                    0,
+                    ogl33::GL_RED,
+                    ogl33::GL_UNSIGNED_BYTE,
+                    empty.as_ptr().cast(),
+                );
+                ogl33::glPixelStorei(ogl33::GL_UNPACK_ALIGNMENT, 4);
+            }
+            Some(Texture { tex_id })
+        } else {
+            None
+        }
+    }
+    //single channel data for textures created by new_alpha, goes through unit 1 so unit 0 keeps its binding
+    pub fn upload_region(&self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        unsafe {
+            ogl33::glActiveTexture(ogl33::GL_TEXTURE1);
+            ogl33::glBindTexture(ogl33::GL_TEXTURE_2D, self.tex_id);
+            ogl33::glPixelStorei(ogl33::GL_UNPACK_ALIGNMENT, 1);
+            ogl33::glTexSubImage2D(
+                ogl33::GL_TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                ogl33::GL_RED,
+                ogl33::GL_UNSIGNED_BYTE,
+                data.as_ptr().cast(),
+            );
+            ogl33::glPixelStorei(ogl33::GL_UNPACK_ALIGNMENT, 4);
+            ogl33::glActiveTexture(ogl33::GL_TEXTURE0);
+        }
+    }
     pub fn bind(&self) {
         unsafe {
             ogl33::glBindTexture(ogl33::GL_TEXTURE_2D, self.tex_id);
         }
     }
+    pub fn bind_unit(&self, unit: u32) {
+        unsafe {
+            ogl33::glActiveTexture(ogl33::GL_TEXTURE0 + unit);
+            ogl33::glBindTexture(ogl33::GL_TEXTURE_2D, self.tex_id);
+            ogl33::glActiveTexture(ogl33::GL_TEXTURE0);
+        }
+    }
 }
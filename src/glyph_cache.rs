@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use rusttype::{Font, GlyphId, Point, Scale};
+
+use crate::glwrappers;
+
+//glyphs are rasterized once at this size and scaled for drawing, like the old atlas glyphs
+pub const GLYPH_RASTER_SIZE: f32 = 32.;
+const CACHE_SIZE: u32 = 1024;
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CachedGlyph {
+    pub uv: (f32, f32, f32, f32),
+    //pixel bounding box relative to the pen position at GLYPH_RASTER_SIZE
+    pub min: (f32, f32),
+    pub size: (f32, f32),
+}
+
+//simple shelf packer, rows are opened top to bottom and filled left to right
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+    shelves: Vec<(u32, u32, u32)>,
+}
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        ShelfPacker {
+            width,
+            height,
+            shelves: Vec::new(),
+        }
+    }
+    pub fn pack(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        if w > self.width {
+            return None;
+        }
+        //shelves are (y, height, used width)
+        for shelf in &mut self.shelves {
+            if h <= shelf.1 && shelf.2 + w <= self.width {
+                let x = shelf.2;
+                shelf.2 += w;
+                return Some((x, shelf.0));
+            }
+        }
+        let y = self
+            .shelves
+            .last()
+            .map(|shelf| shelf.0 + shelf.1)
+            .unwrap_or(0);
+        if y + h > self.height {
+            return None;
+        }
+        self.shelves.push((y, h, w));
+        Some((0, y))
+    }
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+    }
+}
+
+pub struct GlyphCache {
+    texture: glwrappers::Texture,
+    packer: ShelfPacker,
+    glyphs: HashMap<(usize, GlyphId), Option<CachedGlyph>>,
+    needs_reset: bool,
+}
+impl GlyphCache {
+    pub fn new() -> Self {
+        GlyphCache {
+            texture: glwrappers::Texture::new_alpha(CACHE_SIZE, CACHE_SIZE)
+                .expect("couldnt create glyph cache texture"),
+            packer: ShelfPacker::new(CACHE_SIZE, CACHE_SIZE),
+            glyphs: HashMap::new(),
+            needs_reset: false,
+        }
+    }
+    //returns None for glyphs without pixels and while the cache is waiting to be flushed
+    pub fn get(&mut self, fonts: &[Font], font: usize, id: GlyphId) -> Option<CachedGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(font, id)) {
+            return *glyph;
+        }
+        if self.needs_reset {
+            return None;
+        }
+        let glyph = fonts[font]
+            .glyph(id)
+            .scaled(Scale::uniform(GLYPH_RASTER_SIZE))
+            .positioned(Point { x: 0., y: 0. });
+        let Some(bb) = glyph.pixel_bounding_box() else {
+            self.glyphs.insert((font, id), None);
+            return None;
+        };
+        let (w, h) = (bb.width() as u32, bb.height() as u32);
+        let Some((x, y)) = self.packer.pack(w + GLYPH_PADDING, h + GLYPH_PADDING) else {
+            //full, everything gets rasterized again from the next frame on
+            self.needs_reset = true;
+            return None;
+        };
+        let mut pixels = vec![0u8; (w * h) as usize];
+        glyph.draw(|gx, gy, v| {
+            pixels[(gx + gy * w) as usize] = (v * 255.) as u8;
+        });
+        self.texture.upload_region(x, y, w, h, pixels.as_slice());
+        let cached = CachedGlyph {
+            uv: (
+                x as f32 / CACHE_SIZE as f32,
+                y as f32 / CACHE_SIZE as f32,
+                (x + w) as f32 / CACHE_SIZE as f32,
+                (y + h) as f32 / CACHE_SIZE as f32,
+            ),
+            min: (bb.min.x as f32, bb.min.y as f32),
+            size: (w as f32, h as f32),
+        };
+        self.glyphs.insert((font, id), Some(cached));
+        Some(cached)
+    }
+    pub fn flush(&mut self) {
+        if self.needs_reset {
+            self.glyphs.clear();
+            self.packer.clear();
+            self.needs_reset = false;
+        }
+    }
+    pub fn bind(&self, unit: u32) {
+        self.texture.bind_unit(unit);
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 };
 
 use json::JsonValue;
-use rusttype::Scale;
+use rusttype::{GlyphId, Scale};
 use sdl2::keyboard::Keycode;
 use tungstenite::WebSocket;
 use ultraviolet::Vec3;
@@ -15,7 +15,9 @@ use ultraviolet::Vec3;
 use crate::{
     game::{self, AtlassedTexture, BlockRegistry},
     glwrappers,
-    util::{ItemModel, ItemRenderData, ItemSlot, NetworkMessageC2S},
+    glyph_cache::{GlyphCache, GLYPH_RASTER_SIZE},
+    lang::Translations,
+    util::{ItemContext, ItemModel, ItemRenderData, ItemSlot, NetworkMessageC2S},
     TextureAtlas,
 };
 
@@ -61,6 +63,17 @@ impl GUIRenderer {
                     .unwrap(),
                 std::mem::size_of::<[f32; 2 + 2]>() as *const _,
             );
+            ogl33::glVertexAttribPointer(
+                3,
+                1,
+                ogl33::GL_FLOAT,
+                ogl33::GL_FALSE,
+                std::mem::size_of::<glwrappers::GuiVertex>()
+                    .try_into()
+                    .unwrap(),
+                std::mem::size_of::<[f32; 2 + 2 + 4]>() as *const _,
+            );
+            ogl33::glEnableVertexAttribArray(3);
             ogl33::glEnableVertexAttribArray(2);
             ogl33::glEnableVertexAttribArray(1);
             ogl33::glEnableVertexAttribArray(0);
@@ -94,6 +107,7 @@ impl GUIRenderer {
                 quad.color.g,
                 quad.color.b,
                 quad.color.a,
+                if quad.glyph { 1. } else { 0. },
             ]);
             vertices.push([
                 quad.x2 * width_multiplier,
@@ -104,6 +118,7 @@ impl GUIRenderer {
                 quad.color.g,
                 quad.color.b,
                 quad.color.a,
+                if quad.glyph { 1. } else { 0. },
             ]);
             vertices.push([
                 quad.x3 * width_multiplier,
@@ -114,6 +129,7 @@ impl GUIRenderer {
                 quad.color.g,
                 quad.color.b,
                 quad.color.a,
+                if quad.glyph { 1. } else { 0. },
             ]);
             vertices.push([
                 quad.x3 * width_multiplier,
@@ -124,6 +140,7 @@ impl GUIRenderer {
                 quad.color.g,
                 quad.color.b,
                 quad.color.a,
+                if quad.glyph { 1. } else { 0. },
             ]);
             vertices.push([
                 quad.x4 * width_multiplier,
@@ -134,6 +151,7 @@ impl GUIRenderer {
                 quad.color.g,
                 quad.color.b,
                 quad.color.a,
+                if quad.glyph { 1. } else { 0. },
             ]);
             vertices.push([
                 quad.x1 * width_multiplier,
@@ -144,6 +162,7 @@ impl GUIRenderer {
                 quad.color.g,
                 quad.color.b,
                 quad.color.a,
+                if quad.glyph { 1. } else { 0. },
             ]);
         }
         self.vbo.upload_data(
@@ -174,6 +193,7 @@ pub struct GUIQuad {
     u2: f32,
     v2: f32,
     color: Color,
+    glyph: bool,
 }
 impl GUIQuad {
     pub fn new(x: f32, y: f32, w: f32, h: f32, texture: &AtlassedTexture, color: Color) -> GUIQuad {
@@ -192,6 +212,7 @@ impl GUIQuad {
             u2: uv.2,
             v2: uv.3,
             color,
+            glyph: false,
         }
     }
     pub fn new_uv(
@@ -216,6 +237,35 @@ impl GUIQuad {
             u2: uv.2,
             v2: uv.3,
             color,
+            glyph: false,
+        }
+    }
+    //quad with its uvs given per corner, the uvs have to go around an axis aligned rectangle
+    pub fn from_corners(points: [(f32, f32); 4], uvs: [(f32, f32); 4], color: Color) -> GUIQuad {
+        //the renderer expects u to change between the first two corners
+        let (points, uvs) = if uvs[0].1 != uvs[1].1 {
+            (
+                [points[1], points[2], points[3], points[0]],
+                [uvs[1], uvs[2], uvs[3], uvs[0]],
+            )
+        } else {
+            (points, uvs)
+        };
+        GUIQuad {
+            x1: points[0].0,
+            y1: points[0].1,
+            x2: points[1].0,
+            y2: points[1].1,
+            x3: points[2].0,
+            y3: points[2].1,
+            x4: points[3].0,
+            y4: points[3].1,
+            u1: uvs[0].0,
+            v1: uvs[2].1,
+            u2: uvs[2].0,
+            v2: uvs[0].1,
+            color,
+            glyph: false,
         }
     }
 }
@@ -228,11 +278,16 @@ pub enum GUIComponent {
         Color,
         Option<(f32, f32, f32, f32)>,
     ),
-    TextComponent(f32, String, Color, bool),
+    //scale, text, color, center, wrap width
+    TextComponent(f32, String, Color, bool, Option<f32>),
     SlotComponent(f32, Option<ItemSlot>, Color, bool),
 }
 impl GUIComponent {
-    pub fn from_json(json: &JsonValue, texture_atlas: &TextureAtlas) -> GUIComponent {
+    pub fn from_json(
+        json: &JsonValue,
+        texture_atlas: &TextureAtlas,
+        translations: &Translations,
+    ) -> GUIComponent {
         let json_color = &json["color"];
         let color = if json_color.is_null() {
             Color {
@@ -259,9 +314,14 @@ impl GUIComponent {
             ),
             "text" => GUIComponent::TextComponent(
                 1.,
-                String::new(),
+                if json["text"].is_null() {
+                    String::new()
+                } else {
+                    translations.format_json(&json["text"])
+                },
                 color,
                 json["center"].as_bool().unwrap_or(false),
+                json["wrap"].as_f32(),
             ),
             "slot" => {
                 let json_slot = &json["item"];
@@ -296,7 +356,12 @@ impl GUIComponent {
             text => panic!("unknown element type {}", text),
         }
     }
-    pub fn set_data(&mut self, data_type: &str, json: &json::JsonValue) {
+    pub fn set_data(
+        &mut self,
+        data_type: &str,
+        json: &json::JsonValue,
+        translations: &Translations,
+    ) {
         match self {
             Self::ImageComponent(w, h, texture, color, slice) => match data_type {
                 "color" => {
@@ -328,7 +393,7 @@ impl GUIComponent {
                 }
                 _ => {}
             },
-            Self::TextComponent(scale, text, color, center) => match data_type {
+            Self::TextComponent(scale, text, color, center, wrap) => match data_type {
                 "color" => {
                     let json_color = &json["color"];
                     *color = Color {
@@ -339,7 +404,10 @@ impl GUIComponent {
                     };
                 }
                 "text" => {
-                    *text = json["text"].as_str().unwrap().to_string();
+                    *text = translations.format_json(&json["text"]);
+                }
+                "wrap" => {
+                    *wrap = json["wrap"].as_f32();
                 }
                 _ => {}
             },
@@ -393,10 +461,12 @@ impl GUIComponent {
         block_registry: &BlockRegistry,
         x: f32,
         y: f32,
+        time: f32,
     ) {
         match self {
             Self::ImageComponent(w, h, texture, color, slice) => match slice {
                 Some(slice) => {
+                    let texture = texture.frame_at(time);
                     let uv0 = texture.map_uv((slice.0, slice.1));
                     let uv1 = texture.map_uv((slice.2, slice.3));
                     quads.push(GUIQuad::new_uv(
@@ -409,11 +479,15 @@ impl GUIComponent {
                     ));
                 }
                 None => {
-                    quads.push(GUIQuad::new(x, y, *w, *h, &texture, *color));
+                    quads.push(GUIQuad::new(x, y, *w, *h, &texture.frame_at(time), *color));
                 }
             },
-            Self::TextComponent(scale, text, color, center) => {
-                text_renderer.render(x, y, *scale, text, color, *center, quads);
+            Self::TextComponent(scale, text, color, center, wrap) => {
+                let text = match wrap {
+                    Some(wrap) => text_renderer.wrap(text, *scale, *wrap).join("\n"),
+                    None => text.clone(),
+                };
+                text_renderer.render(x, y, *scale, text.as_str(), color, *center, quads);
             }
             Self::SlotComponent(size, item, color, background) => {
                 let size = size * 0.1;
@@ -434,6 +508,7 @@ impl GUIComponent {
                         block_registry,
                         x - border,
                         y - border,
+                        time,
                     );
                 }
                 if let Some(slot) = item {
@@ -460,6 +535,7 @@ impl GUIComponent {
                                 block_registry,
                                 x,
                                 y,
+                                time,
                             );
                         }
                         ItemModel::Block(block_id) => {
@@ -467,9 +543,9 @@ impl GUIComponent {
                             match block.render_type {
                                 game::BlockRenderType::Air => {}
                                 game::BlockRenderType::Cube(_, north, _, right, _, up, _) => {
-                                    let top_texture = up.get_coords();
-                                    let front_texture = north.get_coords();
-                                    let right_texture = right.get_coords();
+                                    let top_texture = up.frame_at(time).get_coords();
+                                    let front_texture = north.frame_at(time).get_coords();
+                                    let right_texture = right.frame_at(time).get_coords();
                                     let middle_x = size * 13. / 26.;
                                     let middle_y = size * 4. / 6.;
                                     quads.push(GUIQuad {
@@ -491,6 +567,7 @@ impl GUIComponent {
                                         v1: top_texture.1,
                                         u2: top_texture.2,
                                         v2: top_texture.3,
+                                        glyph: false,
                                     });
                                     quads.push(GUIQuad {
                                         x1: x + middle_x,
@@ -511,6 +588,7 @@ impl GUIComponent {
                                         v1: front_texture.3,
                                         u2: front_texture.2,
                                         v2: front_texture.1,
+                                        glyph: false,
                                     });
                                     quads.push(GUIQuad {
                                         x1: x,
@@ -531,6 +609,7 @@ impl GUIComponent {
                                         v1: right_texture.3,
                                         u2: right_texture.2,
                                         v2: right_texture.1,
+                                        glyph: false,
                                     });
                                 }
                                 game::BlockRenderType::Foliage(_, _, _, _)
@@ -548,6 +627,38 @@ impl GUIComponent {
                                 ) => {}
                             }
                         }
+                        ItemModel::Model(model) => {
+                            let mut vertices = Vec::new();
+                            model.model.add_vertices_static(
+                                &mut |position, u, v| vertices.push((position, (u, v))),
+                                model.transform(ItemContext::Gui).matrix(),
+                            );
+                            //every face is two triangles, drawn back to front since gui has no depth
+                            let mut faces: Vec<_> = vertices
+                                .chunks_exact(6)
+                                .map(|face| [face[0], face[1], face[2], face[4]])
+                                .collect();
+                            faces.sort_by(|a, b| {
+                                let depth = |face: &[(Vec3, (f32, f32)); 4]| {
+                                    face.iter().map(|v| v.0.z).sum::<f32>()
+                                };
+                                depth(a).total_cmp(&depth(b))
+                            });
+                            for face in faces {
+                                quads.push(GUIQuad::from_corners(
+                                    face.map(|(position, _)| {
+                                        (x + size * (0.5 + position.x), y + size * position.y)
+                                    }),
+                                    face.map(|(_, uv)| uv),
+                                    Color {
+                                        r: 1.,
+                                        g: 1.,
+                                        b: 1.,
+                                        a: 1.,
+                                    },
+                                ));
+                            }
+                        }
                     }
 
                     if slot.count > 1 {
@@ -561,6 +672,7 @@ impl GUIComponent {
                                 a: 1.,
                             },
                             false,
+                            None,
                         );
                         text.add_quads(
                             quads,
@@ -568,8 +680,9 @@ impl GUIComponent {
                             texture_atlas,
                             item_renderer,
                             block_registry,
-                            x + size - text.get_width(),
-                            y + text.get_height(),
+                            x + size - text.get_width(text_renderer),
+                            y + text.get_height(text_renderer),
+                            time,
                         );
                     }
                     if let Some((progress, color_r, color_g, color_b)) = slot.bar {
@@ -593,20 +706,25 @@ impl GUIComponent {
                             block_registry,
                             x + 0.01,
                             y + 0.01,
+                            time,
                         );
                     }
                 }
             }
         }
     }
-    pub fn get_width(&self) -> f32 {
+    pub fn get_width(&self, text_renderer: &TextRenderer) -> f32 {
         match self {
             Self::ImageComponent(w, _, _, _, _) => *w,
-            Self::TextComponent(scale, text, _, _) => text
-                .split('\n')
-                .map(|t| (t.len() as f32) * 0.06 * scale)
-                .reduce(f32::max)
-                .unwrap_or(0.),
+            Self::TextComponent(scale, text, _, _, wrap) => match wrap {
+                Some(wrap) => text_renderer
+                    .wrap(text, *scale, *wrap)
+                    .iter()
+                    .map(|line| text_renderer.measure(line, *scale))
+                    .reduce(f32::max)
+                    .unwrap_or(0.),
+                None => text_renderer.measure(text, *scale),
+            },
             Self::SlotComponent(size, _, _, border) => {
                 let size = size * 0.1;
                 let border = if *border { size * 0.1 } else { 0. };
@@ -614,16 +732,21 @@ impl GUIComponent {
             }
         }
     }
-    pub fn get_height(&self) -> f32 {
+    pub fn get_height(&self, text_renderer: &TextRenderer) -> f32 {
         match self {
             Self::ImageComponent(_, h, _, _, _) => *h,
-            Self::TextComponent(scale, text, _, _) => {
-                text.split('\n').count() as f32 * 0.08f32 * scale
+            Self::TextComponent(scale, text, _, _, wrap) => {
+                let lines = match wrap {
+                    Some(wrap) => text_renderer.wrap(text, *scale, *wrap).len(),
+                    None => text.split('\n').count(),
+                };
+                lines as f32 * text_renderer.line_height(*scale)
             }
-            Self::SlotComponent(_, _, _, _) => self.get_width(),
+            Self::SlotComponent(_, _, _, _) => self.get_width(text_renderer),
         }
     }
 }
+const MAX_ASSET_REPORT_LINES: usize = 30;
 pub struct GUI<'a> {
     renderer: GUIRenderer,
     font_renderer: TextRenderer<'a>,
@@ -639,6 +762,10 @@ pub struct GUI<'a> {
     block_registry: &'a game::BlockRegistry,
     pub gui_scale: f32,
     pub chat: ChatRenderer,
+    translations: &'a Translations,
+    pub show_asset_report: bool,
+    //slot element the server marked as the one in the players hand
+    held_slot: Option<String>,
 }
 impl<'a> GUI<'a> {
     pub fn new(
@@ -649,6 +776,7 @@ impl<'a> GUI<'a> {
         size: (u32, u32),
         window: &'a RefCell<sdl2::video::Window>,
         block_registry: &'a game::BlockRegistry,
+        translations: &'a Translations,
     ) -> Self {
         sdl.mouse().set_relative_mouse_mode(true);
         Self {
@@ -666,15 +794,35 @@ impl<'a> GUI<'a> {
             block_registry,
             gui_scale: 1.5,
             chat: ChatRenderer::new(),
+            translations,
+            show_asset_report: false,
+            held_slot: None,
+        }
+    }
+    pub fn held_item(&self) -> Option<&ItemSlot> {
+        match &self.elements.get(self.held_slot.as_ref()?)?.component {
+            GUIComponent::SlotComponent(_, item, _, _) => item.as_ref(),
+            _ => None,
+        }
+    }
+    fn set_held_slot(&mut self, id: &String, held: bool) {
+        if held {
+            self.held_slot = Some(id.clone());
+        } else if self.held_slot.as_ref() == Some(id) {
+            self.held_slot = None;
         }
     }
     pub fn on_json_data(&mut self, data: JsonValue) {
         match data["type"].as_str().unwrap() {
             "setElement" => {
                 let id = data["id"].as_str().unwrap().to_string();
+                self.texture_atlas
+                    .asset_report
+                    .set_requester(format!("gui element {}", id));
                 match id.as_str() {
                     "cursor" => {
-                        let component = GUIComponent::from_json(&data, &self.texture_atlas);
+                        let component =
+                            GUIComponent::from_json(&data, &self.texture_atlas, self.translations);
                         if let Some(cursor) = &mut self.cursor {
                             cursor.0 = component;
                         } else {
@@ -683,19 +831,27 @@ impl<'a> GUI<'a> {
                     }
                     _ => {
                         if !data["element_type"].is_null() {
-                            let component = GUIComponent::from_json(&data, &self.texture_atlas);
+                            let component = GUIComponent::from_json(
+                                &data,
+                                &self.texture_atlas,
+                                self.translations,
+                            );
                             let element = GUIElement {
                                 component,
                                 x: data["x"].as_f32().unwrap(),
                                 y: data["y"].as_f32().unwrap(),
                                 z: data["z"].as_i32().unwrap_or(0),
                             };
+                            self.set_held_slot(&id, data["held"].as_bool().unwrap_or(false));
                             self.elements.insert(id, element);
                         } else {
                             self.elements.remove(&id);
                         }
                     }
                 }
+                self.texture_atlas
+                    .asset_report
+                    .set_requester(String::from("client"));
             }
             "editElement" => {
                 let id = data["id"].as_str().unwrap().to_string();
@@ -715,19 +871,23 @@ impl<'a> GUI<'a> {
                                     (y * self.size.1 as f32) as i32,
                                 );
                             } else {
-                                cursor.0.set_data(data_type, &data);
+                                cursor.0.set_data(data_type, &data, self.translations);
                             }
                         }
                     }
                     _ => {
-                        if let Some(element) = self.elements.get_mut(&id) {
-                            let data_type = data["data_type"].as_str().unwrap();
+                        let data_type = data["data_type"].as_str().unwrap();
+                        if data_type == "held" {
+                            self.set_held_slot(&id, data["held"].as_bool().unwrap());
+                        } else if let Some(element) = self.elements.get_mut(&id) {
                             if data_type == "position" {
                                 let position = &data["position"];
                                 element.x = position[0].as_f32().unwrap();
                                 element.y = position[1].as_f32().unwrap();
                             } else {
-                                element.component.set_data(data_type, &data);
+                                element
+                                    .component
+                                    .set_data(data_type, &data, self.translations);
                             }
                         }
                     }
@@ -762,7 +922,8 @@ impl<'a> GUI<'a> {
         y: f32,
         z: f32,
         fps: u32,
-        rendered_chunks: (i32, i32, i32, i32, i32),
+        rendered_chunks: (i32, i32, i32, i32, i32, i32, i32),
+        time: f32,
     ) -> Vec<GUIQuad> {
         let mut quads = Vec::new();
         let mut elements: Vec<&GUIElement> = self.elements.values().collect();
@@ -776,6 +937,7 @@ impl<'a> GUI<'a> {
                 self.block_registry,
                 element.x,
                 element.y,
+                time,
             );
         }
         if let Some(cursor) = &self.cursor {
@@ -785,14 +947,15 @@ impl<'a> GUI<'a> {
                 &self.texture_atlas,
                 self.item_renderer,
                 self.block_registry,
-                cursor.1 - (cursor.0.get_width() / 2.),
-                cursor.2 - (cursor.0.get_height() / 2.),
+                cursor.1 - (cursor.0.get_width(&self.font_renderer) / 2.),
+                cursor.2 - (cursor.0.get_height(&self.font_renderer) / 2.),
+                time,
             );
             for element in &elements {
                 if element.x <= cursor.1
-                    && element.x + element.component.get_width() >= cursor.1
+                    && element.x + element.component.get_width(&self.font_renderer) >= cursor.1
                     && element.y <= cursor.2
-                    && element.y + element.component.get_height() >= cursor.2
+                    && element.y + element.component.get_height(&self.font_renderer) >= cursor.2
                 {
                     if let GUIComponent::SlotComponent(_, item, _, background) = &element.component
                     {
@@ -808,6 +971,7 @@ impl<'a> GUI<'a> {
                                         a: 1.,
                                     },
                                     false,
+                                    None,
                                 )
                                 .add_quads(
                                     &mut quads,
@@ -817,6 +981,7 @@ impl<'a> GUI<'a> {
                                     self.block_registry,
                                     cursor.1,
                                     cursor.2,
+                                    time,
                                 )
                             }
                         }
@@ -827,7 +992,7 @@ impl<'a> GUI<'a> {
         GUIComponent::TextComponent(
             0.5,
             format!(
-                "x:{:.2} y:{:.2} z:{:.2} fps:{} s:{} t:{} f:{} a:{} l:{}",
+                "x:{:.2} y:{:.2} z:{:.2} fps:{} s:{} t:{} f:{} a:{} c:{} l:{} m:{}KiB",
                 x,
                 y,
                 z,
@@ -836,7 +1001,9 @@ impl<'a> GUI<'a> {
                 rendered_chunks.1,
                 rendered_chunks.2,
                 rendered_chunks.3,
-                rendered_chunks.4
+                rendered_chunks.5,
+                rendered_chunks.4,
+                rendered_chunks.6
             ),
             Color {
                 r: 0.,
@@ -845,6 +1012,7 @@ impl<'a> GUI<'a> {
                 a: 1.,
             },
             false,
+            None,
         )
         .add_quads(
             &mut quads,
@@ -854,7 +1022,22 @@ impl<'a> GUI<'a> {
             &self.block_registry,
             -1.18,
             0.6,
+            time,
         );
+        if self.show_asset_report {
+            self.asset_report_component().add_quads(
+                &mut quads,
+                &self.font_renderer,
+                &self.texture_atlas,
+                &self.item_renderer,
+                &self.block_registry,
+                -1.18,
+                0.55 - self
+                    .asset_report_component()
+                    .get_height(&self.font_renderer),
+                time,
+            );
+        }
         self.chat.add_quads(
             &mut quads,
             &self.font_renderer,
@@ -863,9 +1046,43 @@ impl<'a> GUI<'a> {
             &self.block_registry,
             -0.8,
             -0.6,
+            time,
         );
         quads
     }
+    fn asset_report_component(&self) -> GUIComponent {
+        let report = &self.texture_atlas.asset_report;
+        let lines = report.lines();
+        let mut text = if lines.is_empty() {
+            String::from("no missing assets")
+        } else {
+            format!("{} missing assets", lines.len())
+        };
+        let warnings = report.warning_lines();
+        if !warnings.is_empty() {
+            text.push_str(format!(", {} warnings", warnings.len()).as_str());
+        }
+        let all: Vec<&String> = lines.iter().chain(warnings.iter()).collect();
+        for line in all.iter().take(MAX_ASSET_REPORT_LINES) {
+            text.push('\n');
+            text.push_str(line);
+        }
+        if all.len() > MAX_ASSET_REPORT_LINES {
+            text.push_str(format!("\n...and {} more", all.len() - MAX_ASSET_REPORT_LINES).as_str());
+        }
+        GUIComponent::TextComponent(
+            0.4,
+            text,
+            Color {
+                r: 0.8,
+                g: 0.,
+                b: 0.,
+                a: 1.,
+            },
+            false,
+            Some(2.3),
+        )
+    }
     pub fn on_mouse_move(&mut self, x: i32, y: i32) -> bool {
         if !self.mouse_locked {
             if let Some(cursor) = &mut self.cursor {
@@ -885,9 +1102,11 @@ impl<'a> GUI<'a> {
                 let mut id = None;
                 for element in &self.elements {
                     if element.1.x <= cursor.1
-                        && element.1.x + element.1.component.get_width() >= cursor.1
+                        && element.1.x + element.1.component.get_width(&self.font_renderer)
+                            >= cursor.1
                         && element.1.y <= cursor.2
-                        && element.1.y + element.1.component.get_height() >= cursor.2
+                        && element.1.y + element.1.component.get_height(&self.font_renderer)
+                            >= cursor.2
                     {
                         id = Some(element.0.clone());
                     }
@@ -923,9 +1142,11 @@ impl<'a> GUI<'a> {
                 let mut id = None;
                 for element in &self.elements {
                     if element.1.x <= cursor.1
-                        && element.1.x + element.1.component.get_width() >= cursor.1
+                        && element.1.x + element.1.component.get_width(&self.font_renderer)
+                            >= cursor.1
                         && element.1.y <= cursor.2
-                        && element.1.y + element.1.component.get_height() >= cursor.2
+                        && element.1.y + element.1.component.get_height(&self.font_renderer)
+                            >= cursor.2
                     {
                         id = Some(element.0.clone());
                     }
@@ -946,63 +1167,141 @@ impl<'a> GUI<'a> {
         shader: &glwrappers::Shader,
         player_pos: &Vec3,
         fps: u32,
-        rendered_chunks: (i32, i32, i32, i32, i32),
+        rendered_chunks: (i32, i32, i32, i32, i32, i32, i32),
+        time: f32,
     ) {
+        shader.use_program();
+        shader.set_uniform_int(
+            shader
+                .get_uniform_location("glyph_texture\0")
+                .expect("glyph texture uniform not found"),
+            1,
+        );
+        let quads = self.to_quad_list(
+            player_pos.x,
+            player_pos.y,
+            player_pos.z,
+            fps,
+            rendered_chunks,
+            time,
+        );
+        self.font_renderer.glyph_cache.borrow().bind(1);
         self.renderer.render(
             shader,
-            self.to_quad_list(
-                player_pos.x,
-                player_pos.y,
-                player_pos.z,
-                fps,
-                rendered_chunks,
-            ),
+            quads,
             (self.size.1 as f32 / self.size.0 as f32) * self.gui_scale,
             self.gui_scale,
         );
+        self.font_renderer.glyph_cache.borrow_mut().flush();
     }
 }
 pub struct TextRenderer<'a> {
-    pub font: rusttype::Font<'a>,
-    pub texture_atlas: &'a TextureAtlas,
+    //the first font is the one from the asset pack, the others are only used for missing characters
+    pub fonts: Vec<rusttype::Font<'a>>,
+    pub glyph_cache: RefCell<GlyphCache>,
 }
 impl<'a> TextRenderer<'a> {
+    pub fn new(fonts: Vec<rusttype::Font<'a>>) -> Self {
+        TextRenderer {
+            fonts,
+            glyph_cache: RefCell::new(GlyphCache::new()),
+        }
+    }
+    fn font_for(&self, character: char) -> usize {
+        self.fonts
+            .iter()
+            .position(|font| font.glyph(character).id().0 != 0)
+            .unwrap_or(0)
+    }
+    //font, glyph and pen position of every character, plus the width of the whole line
+    fn layout_line(&self, line: &str, size: f32) -> (Vec<(usize, GlyphId, f32)>, f32) {
+        let scale = Scale::uniform(0.1 * size);
+        let mut glyphs = Vec::with_capacity(line.len());
+        let mut pen = 0f32;
+        let mut last: Option<(usize, GlyphId)> = None;
+        for character in line.chars() {
+            let font = self.font_for(character);
+            let glyph = self.fonts[font].glyph(character).scaled(scale);
+            if let Some((last_font, last_id)) = last {
+                if last_font == font {
+                    pen += self.fonts[font].pair_kerning(scale, last_id, glyph.id());
+                }
+            }
+            glyphs.push((font, glyph.id(), pen));
+            pen += glyph.h_metrics().advance_width;
+            last = Some((font, glyph.id()));
+        }
+        (glyphs, pen)
+    }
+    pub fn line_height(&self, size: f32) -> f32 {
+        0.08 * size
+    }
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        text.split('\n')
+            .map(|line| self.layout_line(line, size).1)
+            .reduce(f32::max)
+            .unwrap_or(0.)
+    }
+    //greedy word wrap, words longer than a line get broken up between characters
+    pub fn wrap(&self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", line, word)
+                };
+                if self.measure(candidate.as_str(), size) <= max_width {
+                    line = candidate;
+                    continue;
+                }
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                for character in word.chars() {
+                    line.push(character);
+                    if self.measure(line.as_str(), size) > max_width && line.chars().count() > 1 {
+                        line.pop();
+                        lines.push(std::mem::replace(&mut line, character.to_string()));
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
     pub fn render(
         &self,
         x: f32,
         y: f32,
         size: f32,
-        text: &String,
+        text: &str,
         color: &Color,
         center: bool,
         quads: &mut Vec<GUIQuad>,
     ) {
-        let layout = self.font.layout(
-            text,
-            Scale::uniform(0.1 * size),
-            rusttype::Point { x: 0., y: 0. },
-        );
-        let glyphs: Vec<_> = layout.collect();
-        let center_offset_x = if center {
-            glyphs
-                .get(glyphs.len() - 1)
-                .map(|g| (g.position().x + g.unpositioned().h_metrics().advance_width) / 2.)
-        } else {
-            Some(0.)
-        };
-        for glyph in glyphs {
-            if let Some(bb) = glyph.unpositioned().exact_bounding_box() {
-                let texture = self
-                    .texture_atlas
-                    .get(("font_".to_string() + glyph.id().0.to_string().as_str()).as_str());
-                quads.push(GUIQuad::new_uv(
-                    glyph.position().x + x - center_offset_x.unwrap(),
-                    glyph.position().y - bb.max.y + y,
-                    glyph.unpositioned().h_metrics().advance_width,
-                    -bb.min.y + bb.max.y,
-                    texture.get_coords(),
-                    *color,
-                ));
+        let mut glyph_cache = self.glyph_cache.borrow_mut();
+        let pixel_size = 0.1 * size / GLYPH_RASTER_SIZE;
+        let lines: Vec<_> = text.split('\n').collect();
+        for (i, line) in lines.iter().enumerate() {
+            let (glyphs, width) = self.layout_line(line, size);
+            let line_x = if center { x - (width / 2.) } else { x };
+            let baseline = y + ((lines.len() - i - 1) as f32 * self.line_height(size));
+            for (font, id, pen) in glyphs {
+                if let Some(glyph) = glyph_cache.get(self.fonts.as_slice(), font, id) {
+                    let mut quad = GUIQuad::new_uv(
+                        line_x + pen + (glyph.min.0 * pixel_size),
+                        baseline - ((glyph.min.1 + glyph.size.1) * pixel_size),
+                        glyph.size.0 * pixel_size,
+                        glyph.size.1 * pixel_size,
+                        glyph.uv,
+                        *color,
+                    );
+                    quad.glyph = true;
+                    quads.push(quad);
+                }
             }
         }
     }
@@ -1022,6 +1321,7 @@ pub struct Color {
     pub b: f32,
     pub a: f32,
 }
+const CHAT_WIDTH: f32 = 1.2;
 pub struct ChatRenderer {
     messages: Vec<String>,
     current_writing_message: String,
@@ -1044,28 +1344,35 @@ impl ChatRenderer {
         block_registry: &BlockRegistry,
         x: f32,
         y: f32,
+        time: f32,
     ) {
-        for i in 0..(self.messages.len().min(5) as u32) {
-            GUIComponent::TextComponent(
-                1.,
-                self.messages.get(i as usize).unwrap().clone(),
-                Color {
-                    r: 0.,
-                    g: 0.,
-                    b: 0.,
-                    a: 1.,
-                },
-                false,
-            )
-            .add_quads(
-                quads,
-                text_renderer,
-                texture_atlas,
-                item_renderer,
-                block_registry,
-                x,
-                y + ((i + 1) as f32 * 0.07),
-            );
+        let mut row = 0;
+        for message in self.messages.iter().take(5) {
+            for line in text_renderer.wrap(message, 1., CHAT_WIDTH).iter().rev() {
+                row += 1;
+                GUIComponent::TextComponent(
+                    1.,
+                    line.clone(),
+                    Color {
+                        r: 0.,
+                        g: 0.,
+                        b: 0.,
+                        a: 1.,
+                    },
+                    false,
+                    None,
+                )
+                .add_quads(
+                    quads,
+                    text_renderer,
+                    texture_atlas,
+                    item_renderer,
+                    block_registry,
+                    x,
+                    y + (row as f32 * 0.07),
+                    time,
+                );
+            }
         }
         if self.chat_writing_active {
             GUIComponent::TextComponent(
@@ -1078,6 +1385,7 @@ impl ChatRenderer {
                     a: 1.,
                 },
                 false,
+                None,
             )
             .add_quads(
                 quads,
@@ -1087,6 +1395,7 @@ impl ChatRenderer {
                 block_registry,
                 x,
                 y,
+                time,
             );
         }
     }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use json::JsonValue;
+
+pub const DEFAULT_LOCALE: &str = "en_us";
+
+pub struct Translations {
+    selected: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+impl Translations {
+    //tables are the parsed lang/<locale>.json files keyed by locale
+    pub fn new(mut tables: HashMap<String, JsonValue>, locale: &str) -> Self {
+        let mut read_table = |locale: &str| {
+            let mut table = HashMap::new();
+            if let Some(json) = tables.remove(locale) {
+                for (key, value) in json.entries() {
+                    if let Some(value) = value.as_str() {
+                        table.insert(key.to_string(), value.to_string());
+                    }
+                }
+            } else {
+                println!("no translations for locale {}", locale);
+            }
+            table
+        };
+        let fallback = read_table(DEFAULT_LOCALE);
+        let selected = if locale == DEFAULT_LOCALE {
+            HashMap::new()
+        } else {
+            read_table(locale)
+        };
+        Translations { selected, fallback }
+    }
+    //unknown keys are returned as they are, so untranslated content keeps working
+    pub fn translate(&self, key: &str) -> String {
+        self.selected
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+    //replaces {0}, {1}... with the arguments in one pass, so arguments are never scanned for
+    //placeholders themselves, placeholders without an argument are kept as they are
+    pub fn format(&self, key: &str, args: &[String]) -> String {
+        let template = self.translate(key);
+        let mut text = String::with_capacity(template.len());
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let arg = rest.find('}').and_then(|end| {
+                let index = &rest[1..end];
+                if index.is_empty() || !index.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return None;
+                }
+                Some((args.get(index.parse::<usize>().ok()?)?, end + 1))
+            });
+            match arg {
+                Some((arg, len)) => {
+                    text.push_str(arg);
+                    rest = &rest[len..];
+                }
+                None => {
+                    text.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+    //either a key or {"translate": key, "args": [...]}, arguments can be translatable themselves
+    pub fn format_json(&self, json: &JsonValue) -> String {
+        match json {
+            JsonValue::Object(_) => {
+                let args: Vec<String> = json["args"]
+                    .members()
+                    .map(|arg| self.format_json(arg))
+                    .collect();
+                self.format(json["translate"].as_str().unwrap_or(""), args.as_slice())
+            }
+            JsonValue::String(_) | JsonValue::Short(_) => self.translate(json.as_str().unwrap()),
+            json => json.dump(),
+        }
+    }
+    //chat messages are either plain text or a translatable json object
+    pub fn format_message(&self, message: &str) -> String {
+        match json::parse(message) {
+            Ok(json) if json.is_object() && json["translate"].is_string() => {
+                self.format_json(&json)
+            }
+            _ => message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translations() -> Translations {
+        let mut tables = HashMap::new();
+        tables.insert(
+            DEFAULT_LOCALE.to_string(),
+            json::object! {
+                "chat.message": "<{0}> {1}",
+                "broken": "{0} {x} {} {2} {",
+            },
+        );
+        Translations::new(tables, DEFAULT_LOCALE)
+    }
+
+    #[test]
+    fn arguments_are_inserted_in_order() {
+        let args = ["alice".to_string(), "hi".to_string()];
+        assert_eq!(translations().format("chat.message", &args), "<alice> hi");
+    }
+
+    #[test]
+    fn arguments_are_not_scanned_for_placeholders() {
+        let args = ["{1}".to_string(), "hi".to_string()];
+        assert_eq!(translations().format("chat.message", &args), "<{1}> hi");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        let args = ["a".to_string()];
+        assert_eq!(translations().format("broken", &args), "a {x} {} {2} {");
+        assert_eq!(translations().format("missing", &args), "missing");
+    }
+}
@@ -0,0 +1,130 @@
+//level of detail of chunk meshes, level n merges 2^n blocks along each axis into one cube
+pub const MAX_LOD: u8 = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub struct LodSettings {
+    //distance in blocks at which each coarser level starts
+    pub distances: [f32; MAX_LOD as usize],
+    //how far past a border the distance has to be before the level changes
+    pub hysteresis: f32,
+}
+impl LodSettings {
+    pub fn new(distances: [f32; MAX_LOD as usize], hysteresis: f32) -> Self {
+        LodSettings {
+            distances,
+            hysteresis,
+        }
+    }
+    pub fn cell_size(level: u8) -> i32 {
+        1 << level
+    }
+    //level without hysteresis
+    pub fn level_at(&self, distance: f32) -> u8 {
+        self.distances
+            .iter()
+            .filter(|border| distance >= **border)
+            .count() as u8
+    }
+    //going coarser needs the distance to be past a border by the hysteresis, going finer needs
+    //it to be that far in front of it, so chunks near a border keep their level
+    pub fn select(&self, current: u8, distance: f32) -> u8 {
+        let target = self.level_at(distance);
+        let mut level = current.min(MAX_LOD);
+        while level < target && distance >= self.distances[level as usize] + self.hysteresis {
+            level += 1;
+        }
+        while level > target && distance < self.distances[level as usize - 1] - self.hysteresis {
+            level -= 1;
+        }
+        level
+    }
+}
+impl Default for LodSettings {
+    fn default() -> Self {
+        LodSettings::new([128., 256.], 8.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //levels picked walking away from the camera and back in steps of one block
+    fn walk(settings: &LodSettings) -> Vec<(f32, u8, u8)> {
+        let end = settings.distances[MAX_LOD as usize - 1] + settings.hysteresis * 2. + 16.;
+        let mut distances: Vec<f32> = (0..=end as i32).map(|distance| distance as f32).collect();
+        distances.extend(distances.clone().iter().rev());
+        let mut level = 0;
+        let mut changes = Vec::new();
+        for distance in distances {
+            let next = settings.select(level, distance);
+            if next != level {
+                changes.push((distance, level, next));
+            }
+            level = next;
+        }
+        changes
+    }
+
+    #[test]
+    fn each_border_changes_level_once_per_direction() {
+        for settings in [
+            LodSettings::default(),
+            LodSettings::new([32., 64.], 4.),
+            LodSettings::new([100., 101.], 0.),
+        ] {
+            let changes = walk(&settings);
+            let mut expected = Vec::new();
+            for level in 0..MAX_LOD {
+                expected.push((level, level + 1));
+            }
+            for level in (0..MAX_LOD).rev() {
+                expected.push((level + 1, level));
+            }
+            assert_eq!(
+                changes
+                    .iter()
+                    .map(|(_, from, to)| (*from, *to))
+                    .collect::<Vec<_>>(),
+                expected
+            );
+            //coarser only past the border by the hysteresis, finer only that far in front of it
+            for (distance, from, to) in changes {
+                if to > from {
+                    assert!(distance >= settings.distances[from as usize] + settings.hysteresis);
+                } else {
+                    assert!(distance < settings.distances[to as usize] - settings.hysteresis);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wiggling_inside_hysteresis_keeps_level() {
+        let settings = LodSettings::default();
+        for border in settings.distances {
+            for start in [
+                border - settings.hysteresis - 1.,
+                border + settings.hysteresis + 1.,
+            ] {
+                let settled = settings.select(0, start);
+                let mut level = settled;
+                for step in 0..16 {
+                    let offset = if step % 2 == 0 { 0.5 } else { -0.5 };
+                    level = settings.select(level, border + offset * settings.hysteresis);
+                    assert_eq!(level, settled, "level flips around {} blocks", border);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cell_size_doubles_per_level() {
+        assert_eq!(
+            (0..=MAX_LOD)
+                .map(LodSettings::cell_size)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+    }
+}
@@ -7,16 +7,31 @@
     extract_if,
     let_chains
 )]
+mod atlas_cache;
+mod bbm;
+mod diagnostics;
+mod effects;
+mod frustum;
 mod game;
 mod glwrappers;
+mod glyph_cache;
 mod gui;
+mod lang;
+mod lod;
+mod meshing;
+mod mipmap;
 mod model;
+mod palette;
+mod settings;
+mod tint;
 mod util;
+mod visibility;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::net::TcpStream;
 use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Instant;
@@ -38,7 +53,6 @@ use game::StaticBlockModelConnections;
 use glwrappers::Buffer;
 use glwrappers::Vertex;
 use glwrappers::VertexArray;
-use image::DynamicImage;
 use image::EncodableLayout;
 use image::ImageBuffer;
 use image::Rgba;
@@ -50,9 +64,6 @@ use ogl33::c_char;
 use ogl33::c_void;
 use rustc_hash::FxHashMap;
 use rusttype::Glyph;
-use rusttype::GlyphId;
-use rusttype::Point;
-use rusttype::Scale;
 use sdl2::image::LoadSurface;
 use sdl2::keyboard::Keycode;
 use sdl2::mouse::MouseButton;
@@ -60,10 +71,12 @@ use sdl2::surface::Surface;
 use sdl2::sys::KeyCode;
 use sdl2::video::FullscreenType;
 use sdl2::video::SwapInterval;
+use settings::Settings;
 use texture_packer::{exporter::ImageExporter, importer::ImageImporter, texture::Texture};
 use tungstenite::Message;
 use tungstenite::WebSocket;
 use ultraviolet::Mat4;
+use ultraviolet::Vec2;
 use ultraviolet::Vec3;
 use util::*;
 
@@ -72,6 +85,8 @@ use endio::BERead;
 use sdl2::event::*;
 
 const ANTI_ALIAS: bool = false;
+//has to match the size of texture_animations in chunk.vert
+const MAX_TEXTURE_ANIMATIONS: usize = 64;
 
 fn main() {
     let mut args = std::env::args();
@@ -112,16 +127,21 @@ fn main() {
         ogl33::glClearColor(0.2, 0.3, 0.3, 1.0);
         ogl33::glViewport(0, 0, win_width as i32, win_height as i32)
     }
+    let settings = Settings::load();
     let assets = std::path::Path::new(args.next().unwrap().as_str()).to_path_buf();
     let (
         mut sound_manager,
         texture_atlas,
         packed_texture,
-        font,
+        fonts,
         block_registry,
         entity_registry,
         item_registry,
-    ) = load_assets(assets.as_path());
+        translations,
+        effect_registry,
+    ) = load_assets(assets.as_path(), &settings);
+    //shared with the mesh workers
+    let block_registry = Arc::new(block_registry);
     /*assets.push("icon.png");
     {
         window
@@ -172,10 +192,13 @@ fn main() {
         (discord_thread, discord_thread_tx)
     };
 
-    let chunk_shader = glwrappers::Shader::new(
-        include_str!("shaders/chunk.vert").to_string(),
-        include_str!("shaders/chunk.frag").to_string(),
-    );
+    let chunk_shader = {
+        let (vertex, fragment) = effect_registry.build_shaders(
+            include_str!("shaders/chunk.vert"),
+            include_str!("shaders/chunk.frag"),
+        );
+        glwrappers::Shader::new(vertex, fragment)
+    };
     let outline_shader = glwrappers::Shader::new(
         include_str!("shaders/outline.vert").to_string(),
         include_str!("shaders/outline.frag").to_string(),
@@ -184,31 +207,46 @@ fn main() {
         include_str!("shaders/gui.vert").to_string(),
         include_str!("shaders/gui.frag").to_string(),
     );
-    let texture = glwrappers::Texture::new(
-        packed_texture.as_bytes().to_vec(),
-        packed_texture.width(),
-        packed_texture.height(),
+    let texture = glwrappers::Texture::new_mipmapped(
+        texture_atlas
+            .create_mip_chain(&packed_texture, settings.mipmap_levels)
+            .into_iter()
+            .map(|level| {
+                let (width, height) = (level.width(), level.height());
+                (level.into_raw(), width, height)
+            })
+            .collect(),
+        settings.anisotropic_filtering,
     )
     .expect("couldnt load image");
     texture.bind();
+    chunk_shader.use_program();
+    chunk_shader.set_uniform_vec4_array(
+        chunk_shader
+            .get_uniform_location("texture_animations\0")
+            .expect("texture animations uniform not found"),
+        texture_atlas.animation_uniforms().as_slice(),
+    );
+    //unused when no block has an effect
+    if let Some(effect_params) = chunk_shader.get_uniform_location("effect_params\0") {
+        chunk_shader.set_uniform_vec4_array(effect_params, effect_registry.uniforms().as_slice());
+    }
     video_subsystem
         .gl_set_swap_interval(SwapInterval::VSync)
         .unwrap();
     let mut outline_renderer = BlockOutline::new();
-    let mut world = game::World::new(&block_registry);
+    let mut world = game::World::new(&block_registry, settings.mesh_settings());
     let mut event_pump = sdl.event_pump().unwrap();
     let timer = sdl.timer().unwrap();
     let mut gui = gui::GUI::new(
-        gui::TextRenderer {
-            font,
-            texture_atlas: &texture_atlas,
-        },
+        gui::TextRenderer::new(fonts),
         &item_registry,
         texture_atlas.clone(),
         &sdl,
         (win_width, win_height),
         &window,
         &block_registry,
+        &translations,
     );
     let win_id = { window.borrow().id() };
     let mut block_breaking_manager = BlockBreakingManager::new(vec![
@@ -222,6 +260,11 @@ fn main() {
         texture_atlas.get("breaking8").clone(),
         texture_atlas.get("breaking9").clone(),
     ]);
+    texture_atlas.asset_report.log_summary();
+    if settings.strict_assets && !texture_atlas.asset_report.is_empty() {
+        println!("strict_assets is enabled, refusing to start with missing assets");
+        std::process::exit(1);
+    }
     let item_renderer = ItemRenderer {
         items: &item_registry,
         texture_atlas: &texture_atlas,
@@ -282,6 +325,13 @@ fn main() {
                                 }
                                 world.load_chunk(position, blocks);
                             }
+                            NetworkMessageS2C::ChunkBiomes(x, z, columns) => {
+                                let mut biomes = [[tint::ColumnBiome::default(); 16]; 16];
+                                for (i, column) in columns.into_iter().enumerate() {
+                                    biomes[i / 16][i % 16] = column;
+                                }
+                                world.set_biomes(x, z, biomes);
+                            }
                             NetworkMessageS2C::UnloadChunk(x, y, z) => {
                                 world.unload_chunk(ChunkPosition { x, y, z });
                             }
@@ -391,7 +441,8 @@ fn main() {
                                 }
                             }
                             NetworkMessageS2C::ChatMessage(message) => {
-                                gui.chat.add_message(message);
+                                gui.chat
+                                    .add_message(translations.format_message(message.as_str()));
                             }
                             NetworkMessageS2C::PlayerAbilities(speed, movement_type) => {
                                 camera.speed = speed;
@@ -636,6 +687,9 @@ fn main() {
                             if keycode == Keycode::F9 {
                                 orthographic_projection = !orthographic_projection;
                             }
+                            if keycode == Keycode::F8 {
+                                gui.show_asset_report = !gui.show_asset_report;
+                            }
                             gui.chat.on_key(keycode, &mut socket);
                             if !gui.chat.is_active() {
                                 if keycode == Keycode::Escape {
@@ -759,20 +813,18 @@ fn main() {
                 let size = 50.;
                 ultraviolet::projection::orthographic_gl(-size, size, -size, size, 0.01, 1000.)
             };
-            chunk_shader.set_uniform_matrix(
-                projection_view_loc,
-                projection * camera.create_view_matrix(),
-            );
+            let projection_view = projection * camera.create_view_matrix();
+            chunk_shader.set_uniform_matrix(projection_view_loc, projection_view);
+            if let Some(sky_brightness) = chunk_shader.get_uniform_location("sky_brightness\0") {
+                chunk_shader.set_uniform_float(sky_brightness, sky_renderer.sky_brightness());
+            }
+            let frustum = frustum::Frustum::from_matrix(&projection_view);
             let mut rendered_chunks = {
                 world.render(
                     &chunk_shader,
                     (timer.ticks() as f32) / 1000f32,
-                    Position {
-                        x: camera.position.x,
-                        y: camera.position.y,
-                        z: camera.position.z,
-                    }
-                    .to_chunk_pos(),
+                    camera.get_eye(),
+                    &frustum,
                 )
             };
 
@@ -799,6 +851,7 @@ fn main() {
                     &model.1,
                     entity.1.rotation,
                     &entity.1.items,
+                    ItemContext::Held,
                 ));
             }
             let chunks: Vec<_> = world.chunks.iter().map(|chunk| chunk.1.borrow()).collect();
@@ -818,6 +871,7 @@ fn main() {
                         },
                         0.,
                         &block.1.items,
+                        ItemContext::Ground,
                     ));
                 }
             }
@@ -856,7 +910,15 @@ fn main() {
             }
             block_breaking_manager.render(&projection);
             chunk_shader.use_program();
-            world.render_transparent(&chunk_shader, &mut rendered_chunks);
+            world.render_transparent(
+                &chunk_shader,
+                &frustum,
+                {
+                    let eye = camera.get_eye();
+                    Vec3::new(eye.x, eye.y, eye.z)
+                },
+                &mut rendered_chunks,
+            );
             sky_renderer.render(
                 ultraviolet::projection::perspective_gl(
                     90f32.to_radians(),
@@ -866,7 +928,25 @@ fn main() {
                 ) * camera.create_view_matrix_no_pos(),
                 delta_time,
             );
-            gui.render(&gui_shader, &camera.position, last_fps_cnt, rendered_chunks);
+            if let Some(held_item) = gui.held_item() {
+                world_entity_renderer.render_held_item(
+                    &ultraviolet::projection::perspective_gl(
+                        90f32.to_radians(),
+                        (win_width as f32) / (win_height as f32),
+                        0.01,
+                        1000.,
+                    ),
+                    held_item,
+                    &item_renderer,
+                );
+            }
+            gui.render(
+                &gui_shader,
+                &camera.position,
+                last_fps_cnt,
+                rendered_chunks,
+                (timer.ticks() as f32) / 1000f32,
+            );
             {
                 window.borrow().gl_swap_window();
             }
@@ -988,6 +1068,10 @@ impl SkyRenderer {
         vertices.push([p4.0, p4.1, p4.2, p4.3]);
         vertices.push([p1.0, p1.1, p1.2, p1.3]);
     }
+    //same curve the sky color uses, kept above zero so nights aren't pitch black
+    pub fn sky_brightness(&self) -> f32 {
+        (self.time - 1.).abs().max(0.2)
+    }
     pub fn render(&mut self, view: Mat4, delta_time: f32) {
         self.shader.use_program();
         self.vao.bind();
@@ -1011,8 +1095,63 @@ impl SkyRenderer {
 }
 fn pack_textures(
     textures: Vec<(String, Vec<u8>)>,
-    font: &rusttype::Font,
+    texture_animations: HashMap<String, JsonValue>,
+    settings: &Settings,
 ) -> (TextureAtlas, RgbaImage) {
+    let cache_key = atlas_cache::cache_key(&textures, settings);
+    let (mut texture_map, image) = match settings
+        .atlas_cache
+        .then(|| atlas_cache::load(cache_key))
+        .flatten()
+    {
+        Some(cached) => {
+            println!("using cached texture atlas {:016x}", cache_key);
+            cached
+        }
+        None => {
+            let packed = pack_atlas(textures, settings);
+            if settings.atlas_cache {
+                atlas_cache::save(cache_key, &packed.0, &packed.1);
+            }
+            packed
+        }
+    };
+    if settings.dump_atlas {
+        image.save(Path::new("textureatlasdump.png")).unwrap();
+    }
+    let mut texture_animations: Vec<_> = texture_animations
+        .into_iter()
+        .filter(|(name, _)| texture_map.contains_key(name))
+        .collect();
+    texture_animations.sort_by(|a, b| a.0.cmp(&b.0));
+    for (i, (name, animation)) in texture_animations.iter().enumerate() {
+        if i >= MAX_TEXTURE_ANIMATIONS {
+            println!("too many animated textures, {} will be still", name);
+            continue;
+        }
+        let texture = texture_map.get_mut(name).unwrap();
+        let animation = game::TextureAnimation::from_json(
+            animation,
+            (i + 1) as u8,
+            (texture.h / texture.w.max(1)).max(1),
+        );
+        texture.h /= animation.frames;
+        texture.animation = Some(animation);
+    }
+    (
+        TextureAtlas {
+            missing_texture: texture_map.get("missing").unwrap().clone(),
+            textures: texture_map,
+            extrusion: settings.atlas_texture_extrusion,
+            asset_report: Rc::new(diagnostics::AssetReport::new()),
+        },
+        image,
+    )
+}
+fn pack_atlas(
+    textures: Vec<(String, Vec<u8>)>,
+    settings: &Settings,
+) -> (HashMap<String, AtlassedTexture>, RgbaImage) {
     let mut texture_map = std::collections::HashMap::new();
     let mut packer =
         texture_packer::TexturePacker::new_skyline(texture_packer::TexturePackerConfig {
@@ -1020,42 +1159,16 @@ fn pack_textures(
             max_height: 2048,
             allow_rotation: false,
             texture_outlines: false,
-            border_padding: 0,
-            texture_padding: 0,
+            border_padding: settings.atlas_border_padding,
+            texture_padding: settings.atlas_texture_padding,
             trim: false,
-            texture_extrusion: 0,
+            texture_extrusion: settings.atlas_texture_extrusion,
         });
     for (name, data) in textures {
         if let Ok(texture) = ImageImporter::import_from_memory(data.as_slice()) {
             packer.pack_own(name, texture).unwrap();
         }
     }
-    {
-        let glyphs: Vec<_> = (0..font.glyph_count())
-            .map(|i| {
-                font.glyph(GlyphId { 0: i as u16 })
-                    .scaled(Scale::uniform(30.))
-                    .positioned(Point { x: 0., y: 0. })
-            })
-            .collect();
-        for g in glyphs.iter().enumerate() {
-            if let Some(bb) = g.1.pixel_bounding_box() {
-                let mut font_texture =
-                    DynamicImage::new_rgba8(bb.width() as u32, bb.height() as u32);
-                let font_buffer = match &mut font_texture {
-                    DynamicImage::ImageRgba8(buffer) => buffer,
-                    _ => panic!(),
-                };
-                g.1.draw(|x, y, v| {
-                    font_buffer.put_pixel(x, y, Rgba([0, 0, 0, (v * 255f32) as u8]));
-                    //font_buffer.put_pixel(x, y, Rgba([(v * 255f32) as u8, 0, 0, 255]));
-                });
-                packer
-                    .pack_own("font_".to_string() + g.0.to_string().as_str(), font_texture)
-                    .unwrap();
-            }
-        }
-    }
     packer
         .pack_own(
             "missing".to_string(),
@@ -1071,18 +1184,12 @@ fn pack_textures(
             h: frame.frame.h,
             atlas_w: packer.width(),
             atlas_h: packer.height(),
+            animation: None,
         };
         texture_map.insert(name.to_string(), texture);
     }
     let exporter = ImageExporter::export(&packer).unwrap();
-    exporter.save(Path::new("textureatlasdump.png")).unwrap();
-    (
-        TextureAtlas {
-            missing_texture: texture_map.get("missing").unwrap().clone(),
-            textures: texture_map,
-        },
-        exporter.to_rgba8(),
-    )
+    (texture_map, exporter.to_rgba8())
 }
 struct WorldEntityRenderer {
     vao: glwrappers::VertexArray,
@@ -1163,25 +1270,56 @@ impl WorldEntityRenderer {
             &model::Model,
             f32,
             &HashMap<u32, ItemSlot>,
+            ItemContext,
         )>,
         item_renderer: &ItemRenderer,
     ) {
         let mut vertices: Vec<glwrappers::BasicVertex> = Vec::new();
-        let mut vertex_count = 0;
         for model in models {
             model.2.add_vertices(
                 &mut |pos, u, v| {
                     vertices.push([pos.x, pos.y, pos.z, u, v]);
-                    vertex_count += 1;
                 },
                 model.1,
                 Vec3::new(model.0.x, model.0.y, model.0.z),
                 Vec3::new(0., model.3.to_radians(), 0.),
                 Vec3::new(0., 0., 0.),
                 Vec3::new(1., 1., 1.),
-                Some((model.4, item_renderer)),
+                Some((model.4, item_renderer, model.5)),
             );
         }
+        self.draw(projection, &vertices);
+    }
+    //draws the item in the players hand on top of the world
+    pub fn render_held_item(
+        &mut self,
+        projection: &Mat4,
+        item: &ItemSlot,
+        item_renderer: &ItemRenderer,
+    ) {
+        let mut vertices: Vec<glwrappers::BasicVertex> = Vec::new();
+        //model items bring their own first person transform, the rest use the default one,
+        //either way it is applied outside of the item so both end up in the same place
+        let matrix = match item_renderer.items.get(&item.item).map(|item| &item.model) {
+            Some(ItemModel::Model(model)) => model.transform(ItemContext::FirstPerson).matrix(),
+            _ => ItemTransform::first_person().matrix(),
+        };
+        item_renderer.add_vertices(
+            &mut |pos, u, v| {
+                vertices.push([pos.x, pos.y, pos.z, u, v]);
+            },
+            item,
+            &matrix,
+            &Vec3::new(0., 0., 0.),
+            &Vec2::new(0.5, 0.5),
+            ItemContext::FirstPerson,
+        );
+        unsafe {
+            ogl33::glClear(ogl33::GL_DEPTH_BUFFER_BIT);
+        }
+        self.draw(projection, &vertices);
+    }
+    fn draw(&mut self, projection: &Mat4, vertices: &Vec<glwrappers::BasicVertex>) {
         self.vao.bind();
         self.vbo.upload_data(
             bytemuck::cast_slice(vertices.as_slice()),
@@ -1198,7 +1336,7 @@ impl WorldEntityRenderer {
             ogl33::glDisable(ogl33::GL_CULL_FACE);
             ogl33::glBlendFunc(ogl33::GL_SRC_ALPHA, ogl33::GL_ONE_MINUS_SRC_ALPHA);
             ogl33::glEnable(ogl33::GL_BLEND);
-            ogl33::glDrawArrays(ogl33::GL_TRIANGLES, 0, vertex_count);
+            ogl33::glDrawArrays(ogl33::GL_TRIANGLES, 0, vertices.len() as i32);
             ogl33::glDisable(ogl33::GL_BLEND);
         }
     }
@@ -1207,10 +1345,60 @@ impl WorldEntityRenderer {
 pub struct TextureAtlas {
     textures: HashMap<String, AtlassedTexture>,
     missing_texture: AtlassedTexture,
+    extrusion: u32,
+    //shared between clones, so lookups from the gui end up in the same report
+    pub asset_report: Rc<diagnostics::AssetReport>,
 }
 impl TextureAtlas {
+    #[track_caller]
     pub fn get(&self, texture: &str) -> &AtlassedTexture {
-        self.textures.get(texture).unwrap_or(&self.missing_texture)
+        match self.textures.get(texture) {
+            Some(texture) => texture,
+            None => {
+                self.asset_report.record(
+                    diagnostics::AssetKind::Texture,
+                    texture,
+                    String::from("not found"),
+                    std::panic::Location::caller(),
+                );
+                &self.missing_texture
+            }
+        }
+    }
+    pub fn create_mip_chain(&self, image: &RgbaImage, max_levels: u32) -> Vec<RgbaImage> {
+        let levels = mipmap::max_mip_levels(
+            self.textures.values().map(|texture| (texture.w, texture.h)),
+            max_levels,
+        );
+        let tiles: Vec<_> = self
+            .textures
+            .values()
+            .map(|texture| {
+                mipmap::MipTile::with_extrusion(
+                    texture.x,
+                    texture.y,
+                    texture.w,
+                    texture.strip_height(),
+                    self.extrusion,
+                )
+            })
+            .collect();
+        mipmap::generate_mip_chain(image, tiles.as_slice(), levels)
+    }
+    //frames, frame time, interpolate and frame height in uv space for every animated texture
+    pub fn animation_uniforms(&self) -> Vec<[f32; 4]> {
+        let mut uniforms = vec![[1., 1., 0., 0.]; MAX_TEXTURE_ANIMATIONS];
+        for texture in self.textures.values() {
+            if let Some(animation) = texture.animation {
+                uniforms[(animation.id - 1) as usize] = [
+                    animation.frames as f32,
+                    animation.frame_time,
+                    if animation.interpolate { 1. } else { 0. },
+                    texture.h as f32 / texture.atlas_h as f32,
+                ];
+            }
+        }
+        uniforms
     }
 }
 pub enum HitResult {
@@ -1735,16 +1923,24 @@ impl BlockBreakingManager {
     }
 }
 
+//json files of the asset pack, a broken one is skipped instead of stopping the client
+fn parse_pack_json(data: Vec<u8>) -> Result<JsonValue, String> {
+    let text = String::from_utf8(data).map_err(|_| String::from("not utf-8"))?;
+    json::parse(text.as_str()).map_err(|error| error.to_string())
+}
 fn load_assets(
     zip_path: &Path,
+    settings: &Settings,
 ) -> (
     SoundManager,
     TextureAtlas,
     ImageBuffer<Rgba<u8>, Vec<u8>>,
-    rusttype::Font,
+    Vec<rusttype::Font<'static>>,
     BlockRegistry,
     HashMap<u32, (EntityRenderData, model::Model)>,
     HashMap<u32, ItemRenderData>,
+    lang::Translations,
+    effects::EffectRegistry,
 ) {
     let mut zip =
         zip::ZipArchive::new(std::fs::File::open(zip_path).expect("asset archive not found"))
@@ -1755,6 +1951,10 @@ fn load_assets(
 
     let mut content = None;
     let mut font = None;
+    let mut fallback_fonts = Vec::new();
+    let mut texture_animations = HashMap::new();
+    let mut translation_tables = HashMap::new();
+    let mut effect_sources: HashMap<String, (Option<String>, Option<String>)> = HashMap::new();
 
     for file in 0..zip.len() {
         let mut file = zip.by_index(file).unwrap();
@@ -1766,6 +1966,41 @@ fn load_assets(
         file.read_to_end(&mut data).unwrap();
         let name = file.name();
         println!("name: {}", name);
+        if name.ends_with(".anim.json") {
+            match parse_pack_json(data) {
+                Ok(animation) => {
+                    texture_animations.insert(name.replace(".anim.json", ""), animation);
+                }
+                Err(error) => println!("skipping texture animation {}: {}", name, error),
+            }
+            continue;
+        }
+        if name.starts_with("lang/") && name.ends_with(".json") {
+            match parse_pack_json(data) {
+                Ok(table) => {
+                    translation_tables.insert(
+                        name.replace("lang/", "")
+                            .replace(".json", "")
+                            .to_lowercase(),
+                        table,
+                    );
+                }
+                Err(error) => println!("skipping translations {}: {}", name, error),
+            }
+            continue;
+        }
+        if name.starts_with("effects/") && (name.ends_with(".vert") || name.ends_with(".frag")) {
+            let source = String::from_utf8(data).unwrap();
+            let sources = effect_sources
+                .entry(name[8..name.len() - 5].to_string())
+                .or_default();
+            if name.ends_with(".vert") {
+                sources.0 = Some(source);
+            } else {
+                sources.1 = Some(source);
+            }
+            continue;
+        }
         if name.ends_with(".png") {
             textures_to_pack.push((name.replace(".png", ""), data));
             continue;
@@ -1774,6 +2009,10 @@ fn load_assets(
             sound_manager.load(name.replace(".wav", ""), data);
             continue;
         }
+        if name.ends_with(".ogg") {
+            sound_manager.load(name.replace(".ogg", ""), data);
+            continue;
+        }
         if name.ends_with(".bbm") {
             models.insert(name.replace(".bbm", ""), data);
             continue;
@@ -1786,18 +2025,47 @@ fn load_assets(
             font = Some(rusttype::Font::try_from_vec(data).unwrap());
             continue;
         }
+        if name.starts_with("fonts/") && name.ends_with(".ttf") {
+            match rusttype::Font::try_from_vec(data) {
+                Some(fallback_font) => fallback_fonts.push((name.to_string(), fallback_font)),
+                None => println!("couldnt load font {}", name),
+            }
+            continue;
+        }
+    }
+    fallback_fonts.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut fonts = vec![font.unwrap()];
+    fonts.extend(fallback_fonts.into_iter().map(|(_, font)| font));
+    let (texture_atlas, texture) = pack_textures(textures_to_pack, texture_animations, settings);
+    let translations = lang::Translations::new(translation_tables, settings.locale.as_str());
+    let mut effect_registry = effects::EffectRegistry::new();
+    for (name, (vertex, fragment)) in effect_sources {
+        effect_registry.register(effects::EffectDefinition {
+            name,
+            vertex,
+            fragment,
+            amplitude: 1.,
+            speed: 1.,
+        });
     }
-    let font = font.unwrap();
-    let (texture_atlas, texture) = pack_textures(textures_to_pack, &font);
-    let content = load_content(content.unwrap(), &texture_atlas, &texture, models);
+    let content = load_content(
+        content.unwrap(),
+        &texture_atlas,
+        &texture,
+        models,
+        &translations,
+        &mut effect_registry,
+    );
     (
         sound_manager,
         texture_atlas,
         texture,
-        font,
+        fonts,
         content.0,
         content.1,
         content.2,
+        translations,
+        effect_registry,
     )
 }
 fn load_content(
@@ -1805,133 +2073,92 @@ fn load_content(
     texture_atlas: &TextureAtlas,
     texture: &RgbaImage,
     models: HashMap<String, Vec<u8>>,
+    translations: &lang::Translations,
+    effects: &mut effects::EffectRegistry,
 ) -> (
     BlockRegistry,
     HashMap<u32, (EntityRenderData, model::Model)>,
     HashMap<u32, ItemRenderData>,
 ) {
     let mut block_registry = BlockRegistry { blocks: Vec::new() };
-    block_registry.blocks.insert(0, Block::new_air());
+    //variants get their own ids, so the registry is sized by the largest one
+    let max_id = content["blocks"]
+        .members()
+        .flat_map(|block| {
+            std::iter::once(block["id"].as_u32().unwrap()).chain(
+                block["variants"]
+                    .members()
+                    .map(|variant| variant["id"].as_u32().unwrap()),
+            )
+        })
+        .max()
+        .unwrap_or(0);
     block_registry
         .blocks
-        .resize(content["blocks"].len() + 1, Block::new_air());
+        .resize(max_id as usize + 1, Block::new_air());
+    let mut colormaps = HashMap::new();
+    for (name, defaults) in content["effects"].entries() {
+        effects.set_defaults(name, defaults);
+    }
+    //tags are collected first, connections can refer to blocks defined later in the file
+    let mut tags: HashMap<String, Vec<u32>> = HashMap::new();
+    for block in content["blocks"].members() {
+        for tag in block["tags"].members() {
+            let ids = tags.entry(tag.as_str().unwrap().to_string()).or_default();
+            ids.push(block["id"].as_u32().unwrap());
+            for variant in block["variants"].members() {
+                ids.push(variant["id"].as_u32().unwrap());
+            }
+        }
+    }
     for block in content["blocks"].members() {
         let id = block["id"].as_u32().unwrap();
         let model = &block["model"];
-        let render_type = match model["type"].as_str().unwrap() {
-            "air" => BlockRenderType::Air,
-            "cube" => BlockRenderType::Cube(
-                model["transparent"].as_bool().unwrap_or(false),
-                texture_atlas.get(model["north"].as_str().unwrap()).clone(),
-                texture_atlas.get(model["south"].as_str().unwrap()).clone(),
-                texture_atlas.get(model["right"].as_str().unwrap()).clone(),
-                texture_atlas.get(model["left"].as_str().unwrap()).clone(),
-                texture_atlas.get(model["up"].as_str().unwrap()).clone(),
-                texture_atlas.get(model["down"].as_str().unwrap()).clone(),
-            ),
-            "static" => BlockRenderType::StaticModel(
-                model["transparent"].as_bool().unwrap_or(false),
-                Model::new(
-                    {
-                        match model["model"].as_str() {
-                            Some(model) => models
-                                .get(model)
-                                .map(|data| data.clone())
-                                .unwrap_or(include_bytes!("missing.bbm").to_vec()),
-                            None => include_bytes!("missing.bbm").to_vec(),
-                        }
-                    },
-                    texture_atlas
-                        .get(model["texture"].as_str().unwrap())
-                        .clone(),
-                    Vec::new(),
-                    Vec::new(),
-                ),
-                model["north"].as_bool().unwrap_or(false),
-                model["south"].as_bool().unwrap_or(false),
-                model["right"].as_bool().unwrap_or(false),
-                model["left"].as_bool().unwrap_or(false),
-                model["up"].as_bool().unwrap_or(false),
-                model["down"].as_bool().unwrap_or(false),
-                StaticBlockModelConnections {
-                    front: HashMap::new(),
-                    back: HashMap::new(),
-                    left: HashMap::new(),
-                    right: HashMap::new(),
-                    up: HashMap::new(),
-                    down: HashMap::new(),
-                },
-                model["foliage"].as_bool().unwrap_or(false),
-            ),
-            "foliage" => BlockRenderType::Foliage(
-                model["texture1"]
-                    .as_str()
-                    .map(|t| texture_atlas.get(t).clone()),
-                model["texture2"]
-                    .as_str()
-                    .map(|t| texture_atlas.get(t).clone()),
-                model["texture3"]
-                    .as_str()
-                    .map(|t| texture_atlas.get(t).clone()),
-                model["texture4"]
-                    .as_str()
-                    .map(|t| texture_atlas.get(t).clone()),
-            ),
-            _ => panic!("unknown render type {}", model["type"].as_str().unwrap()),
-        };
-        let dynamic = {
-            let dynamic = &model["dynamic"];
-            if dynamic.is_null() {
-                None
-            } else {
-                Some(Model::new(
-                    {
-                        match dynamic["model"].as_str() {
-                            Some(model) => models
-                                .get(model)
-                                .map(|data| data.clone())
-                                .unwrap_or(include_bytes!("missing.bbm").to_vec()),
-                            None => include_bytes!("missing.bbm").to_vec(),
-                        }
-                    },
-                    texture_atlas
-                        .get(dynamic["texture"].as_str().unwrap())
-                        .clone(),
-                    dynamic["animations"]
-                        .members()
-                        .map(|animation| animation.as_str().unwrap().to_string())
-                        .collect(),
-                    dynamic["items"]
-                        .members()
-                        .map(|item| item.as_str().unwrap().to_string())
-                        .collect(),
-                ))
+        texture_atlas
+            .asset_report
+            .set_requester(format!("block {}", id));
+        block_registry.blocks[id as usize] = load_block(
+            model,
+            BlockRotation::default(),
+            texture_atlas,
+            &models,
+            &tags,
+            texture,
+            &mut colormaps,
+            effects,
+        );
+        for variant in block["variants"].members() {
+            let mut variant_model = model.clone();
+            for (key, texture) in variant["textures"].entries() {
+                variant_model[key] = texture.clone();
             }
-        };
-        block_registry.blocks[id as usize] = Block {
-            render_data: model["render_data"].as_u8().unwrap_or(0),
-            render_type,
-            fluid: model["fluid"].as_bool().unwrap_or(false),
-            no_collision: model["no_collide"].as_bool().unwrap_or(false),
-            selectable: model["selectable"].as_bool().unwrap_or(true),
-            light: {
-                let light = &model["light"];
-                if !light.is_null() {
-                    (
-                        light[0].as_u8().unwrap().min(15),
-                        light[1].as_u8().unwrap().min(15),
-                        light[2].as_u8().unwrap().min(15),
-                    )
-                } else {
-                    (0, 0, 0)
-                }
-            },
-            dynamic,
-        };
+            texture_atlas.asset_report.set_requester(format!(
+                "block {} variant {}",
+                id,
+                variant["id"].as_u32().unwrap()
+            ));
+            block_registry.blocks[variant["id"].as_u32().unwrap() as usize] = load_block(
+                &variant_model,
+                BlockRotation::from_degrees(
+                    variant["x"].as_i32().unwrap_or(0),
+                    variant["y"].as_i32().unwrap_or(0),
+                    variant["uvlock"].as_bool().unwrap_or(false),
+                ),
+                texture_atlas,
+                &models,
+                &tags,
+                texture,
+                &mut colormaps,
+                effects,
+            );
+        }
     }
     let mut entity_registry: HashMap<u32, (EntityRenderData, model::Model)> = HashMap::new();
     for entity in content["entities"].members() {
         let id = entity["id"].as_u32().unwrap();
+        texture_atlas
+            .asset_report
+            .set_requester(format!("entity {}", id));
         let entity_render_data = EntityRenderData {
             model: entity["model"].as_str().unwrap().to_string(),
             texture: entity["texture"].as_str().unwrap().to_string(),
@@ -1940,9 +2167,11 @@ fn load_content(
             hitbox_d: entity["hitboxD"].as_f32().unwrap(),
         };
         let model = match models.get(&entity_render_data.model) {
-            Some(data) => (
-                Model::new(
-                    data.clone(),
+            Some(_) => (
+                load_model(
+                    &models,
+                    &texture_atlas.asset_report,
+                    Some(entity_render_data.model.as_str()),
                     texture_atlas.get(&entity_render_data.texture).clone(),
                     {
                         let mut animations = Vec::new();
@@ -1965,9 +2194,12 @@ fn load_content(
                 ),
                 entity_render_data,
             ),
+            //still passes the name so the missing model ends up in the report
             None => (
-                Model::new(
-                    include_bytes!("missing.bbm").to_vec(),
+                load_model(
+                    &models,
+                    &texture_atlas.asset_report,
+                    Some(entity_render_data.model.as_str()),
                     texture_atlas.missing_texture.clone(),
                     Vec::new(),
                     Vec::new(),
@@ -1980,18 +2212,346 @@ fn load_content(
     let mut item_registry: HashMap<u32, ItemRenderData> = HashMap::new();
     for item in content["items"].members() {
         let id = item["id"].as_u32().unwrap();
+        texture_atlas
+            .asset_report
+            .set_requester(format!("item {}", id));
         let item_render_data = ItemRenderData {
-            name: item["name"].as_str().unwrap().to_string(),
-            model: match item["modelType"].as_str().unwrap() {
+            name: translations.translate(item["name"].as_str().unwrap()),
+            model: match item["modelType"].as_str().unwrap_or("") {
                 "texture" => ItemModel::build_texture(
                     texture_atlas.get(item["modelValue"].as_str().unwrap()),
                     texture,
                 ),
                 "block" => ItemModel::Block(item["modelValue"].as_u32().unwrap()),
-                _ => unreachable!(),
+                "model" => ItemModel::Model(ItemModelData {
+                    model: load_model(
+                        &models,
+                        &texture_atlas.asset_report,
+                        item["modelValue"].as_str(),
+                        texture_atlas.get(item["texture"].as_str().unwrap()).clone(),
+                        Vec::new(),
+                        Vec::new(),
+                    ),
+                    gui: ItemTransform::from_json(
+                        &item["transforms"]["gui"],
+                        ItemTransform::new(
+                            Vec3::new(0., 0., 0.),
+                            Vec3::new(30., 45., 0.),
+                            Vec3::new(0.625, 0.625, 0.625),
+                        ),
+                    ),
+                    held: ItemTransform::from_json(
+                        &item["transforms"]["held"],
+                        ItemTransform::new(
+                            Vec3::new(0., 0., 0.),
+                            Vec3::new(0., 0., 0.),
+                            Vec3::new(1., 1., 1.),
+                        ),
+                    ),
+                    ground: ItemTransform::from_json(
+                        &item["transforms"]["ground"],
+                        ItemTransform::new(
+                            Vec3::new(0., 0., 0.),
+                            Vec3::new(0., 0., 0.),
+                            Vec3::new(0.5, 0.5, 0.5),
+                        ),
+                    ),
+                    first_person: ItemTransform::from_json(
+                        &item["transforms"]["first_person"],
+                        ItemTransform::first_person(),
+                    ),
+                }),
+                model_type => {
+                    texture_atlas.asset_report.record(
+                        diagnostics::AssetKind::Model,
+                        model_type,
+                        String::from("is not an item model type, drawn as the missing texture"),
+                        std::panic::Location::caller(),
+                    );
+                    ItemModel::build_texture(&texture_atlas.missing_texture, texture)
+                }
             },
         };
         item_registry.insert(id, item_render_data);
     }
+    texture_atlas
+        .asset_report
+        .set_requester(String::from("client"));
     (block_registry, entity_registry, item_registry)
 }
+fn load_block(
+    model: &JsonValue,
+    rotation: BlockRotation,
+    texture_atlas: &TextureAtlas,
+    models: &HashMap<String, Vec<u8>>,
+    tags: &HashMap<String, Vec<u32>>,
+    texture: &RgbaImage,
+    colormaps: &mut HashMap<String, Arc<tint::Colormap>>,
+    effects: &mut effects::EffectRegistry,
+) -> Block {
+    let render_type = match model["type"].as_str().unwrap() {
+        "air" => BlockRenderType::Air,
+        "cube" => BlockRenderType::Cube(
+            model["transparent"].as_bool().unwrap_or(false),
+            texture_atlas.get(model["north"].as_str().unwrap()).clone(),
+            texture_atlas.get(model["south"].as_str().unwrap()).clone(),
+            texture_atlas.get(model["right"].as_str().unwrap()).clone(),
+            texture_atlas.get(model["left"].as_str().unwrap()).clone(),
+            texture_atlas.get(model["up"].as_str().unwrap()).clone(),
+            texture_atlas.get(model["down"].as_str().unwrap()).clone(),
+        ),
+        "static" => BlockRenderType::StaticModel(
+            model["transparent"].as_bool().unwrap_or(false),
+            load_model(
+                models,
+                &texture_atlas.asset_report,
+                model["model"].as_str(),
+                texture_atlas
+                    .get(model["texture"].as_str().unwrap())
+                    .clone(),
+                Vec::new(),
+                Vec::new(),
+            ),
+            model["north"].as_bool().unwrap_or(false),
+            model["south"].as_bool().unwrap_or(false),
+            model["right"].as_bool().unwrap_or(false),
+            model["left"].as_bool().unwrap_or(false),
+            model["up"].as_bool().unwrap_or(false),
+            model["down"].as_bool().unwrap_or(false),
+            load_connections(&model["connections"], &model, texture_atlas, models, tags),
+            model["foliage"].as_bool().unwrap_or(false),
+        ),
+        "foliage" => BlockRenderType::Foliage(
+            model["texture1"]
+                .as_str()
+                .map(|t| texture_atlas.get(t).clone()),
+            model["texture2"]
+                .as_str()
+                .map(|t| texture_atlas.get(t).clone()),
+            model["texture3"]
+                .as_str()
+                .map(|t| texture_atlas.get(t).clone()),
+            model["texture4"]
+                .as_str()
+                .map(|t| texture_atlas.get(t).clone()),
+        ),
+        _ => panic!("unknown render type {}", model["type"].as_str().unwrap()),
+    };
+    let dynamic = {
+        let dynamic = &model["dynamic"];
+        if dynamic.is_null() {
+            None
+        } else {
+            Some(load_model(
+                models,
+                &texture_atlas.asset_report,
+                dynamic["model"].as_str(),
+                texture_atlas
+                    .get(dynamic["texture"].as_str().unwrap())
+                    .clone(),
+                dynamic["animations"]
+                    .members()
+                    .map(|animation| animation.as_str().unwrap().to_string())
+                    .collect(),
+                dynamic["items"]
+                    .members()
+                    .map(|item| item.as_str().unwrap().to_string())
+                    .collect(),
+            ))
+        }
+    };
+    Block {
+        render_data: if model["effect"].is_null() {
+            effects.resolve_legacy(model["render_data"].as_u8().unwrap_or(0))
+        } else {
+            effects.resolve(&model["effect"])
+        },
+        render_type,
+        fluid: model["fluid"].as_bool().unwrap_or(false),
+        no_collision: model["no_collide"].as_bool().unwrap_or(false),
+        selectable: model["selectable"].as_bool().unwrap_or(true),
+        light: {
+            let light = &model["light"];
+            if !light.is_null() {
+                (
+                    light[0].as_u8().unwrap().min(15),
+                    light[1].as_u8().unwrap().min(15),
+                    light[2].as_u8().unwrap().min(15),
+                )
+            } else {
+                (0, 0, 0)
+            }
+        },
+        dynamic,
+        rotation,
+        tint: load_tint(&model["tint"], texture_atlas, texture, colormaps),
+    }
+}
+//models that are missing or dont parse are replaced by the bundled missing.bbm
+#[track_caller]
+fn load_model(
+    models: &HashMap<String, Vec<u8>>,
+    report: &diagnostics::AssetReport,
+    name: Option<&str>,
+    texture: AtlassedTexture,
+    animations: Vec<String>,
+    items: Vec<String>,
+) -> Model {
+    if let Some(name) = name {
+        match models.get(name) {
+            Some(data) => match Model::new(
+                data.clone(),
+                texture.clone(),
+                animations.clone(),
+                items.clone(),
+            ) {
+                Ok(model) => return model,
+                //old asset packs still load, but are listed as warnings until they are upgraded
+                Err(bbm::ModelError::BadMagic) => match Model::new_legacy(
+                    data.clone(),
+                    texture.clone(),
+                    animations.clone(),
+                    items.clone(),
+                ) {
+                    Ok(model) => {
+                        report.warn(
+                            diagnostics::AssetKind::Model,
+                            name,
+                            String::from(
+                                "has no bbm header, loaded with the legacy layout, upgrade it with bbmodel_to_bbm --upgrade",
+                            ),
+                            std::panic::Location::caller(),
+                        );
+                        return model;
+                    }
+                    Err(error) => report.record(
+                        diagnostics::AssetKind::Model,
+                        name,
+                        error.to_string(),
+                        std::panic::Location::caller(),
+                    ),
+                },
+                Err(error) => report.record(
+                    diagnostics::AssetKind::Model,
+                    name,
+                    error.to_string(),
+                    std::panic::Location::caller(),
+                ),
+            },
+            None => report.record(
+                diagnostics::AssetKind::Model,
+                name,
+                String::from("not found"),
+                std::panic::Location::caller(),
+            ),
+        }
+    }
+    Model::new(
+        include_bytes!("missing.bbm").to_vec(),
+        texture,
+        animations,
+        items,
+    )
+    .expect("bundled missing.bbm is broken")
+}
+//face names used by content.json
+const CONTENT_FACES: [(Face, &str); 6] = [
+    (Face::Front, "north"),
+    (Face::Back, "south"),
+    (Face::Right, "right"),
+    (Face::Left, "left"),
+    (Face::Up, "up"),
+    (Face::Down, "down"),
+];
+//{"type": "fixed", "color": [r, g, b]}, {"type": "colormap", "colormap": texture} or {"type": "server"},
+//"faces" limits the tint to some faces of a cube
+fn load_tint(
+    json: &JsonValue,
+    texture_atlas: &TextureAtlas,
+    texture: &RgbaImage,
+    colormaps: &mut HashMap<String, Arc<tint::Colormap>>,
+) -> tint::BlockTint {
+    let source = match json["type"].as_str() {
+        None => return tint::BlockTint::none(),
+        Some("fixed") => tint::TintSource::Fixed([
+            json["color"][0].as_u8().unwrap_or(255),
+            json["color"][1].as_u8().unwrap_or(255),
+            json["color"][2].as_u8().unwrap_or(255),
+        ]),
+        Some("colormap") => {
+            let name = json["colormap"].as_str().unwrap();
+            tint::TintSource::Colormap(
+                colormaps
+                    .entry(name.to_string())
+                    .or_insert_with(|| {
+                        Arc::new(tint::Colormap::from_atlas(texture, texture_atlas.get(name)))
+                    })
+                    .clone(),
+            )
+        }
+        Some("server") => tint::TintSource::Server,
+        Some(tint) => panic!("unknown tint type {}", tint),
+    };
+    let faces = if json["faces"].is_null() {
+        0b111111
+    } else {
+        CONTENT_FACES
+            .iter()
+            .filter(|(_, name)| json["faces"].members().any(|face| face == *name))
+            .fold(0, |faces, (face, _)| faces | 1 << *face as u8)
+    };
+    tint::BlockTint { source, faces }
+}
+//connections are listed per face as {"blocks": [id or "#tag"], "model": name, "texture": name},
+//the model is drawn on that face when the neighbouring block matches
+fn load_connections(
+    json: &JsonValue,
+    model: &JsonValue,
+    texture_atlas: &TextureAtlas,
+    models: &HashMap<String, Vec<u8>>,
+    tags: &HashMap<String, Vec<u32>>,
+) -> StaticBlockModelConnections {
+    let mut connections = StaticBlockModelConnections {
+        front: HashMap::new(),
+        back: HashMap::new(),
+        left: HashMap::new(),
+        right: HashMap::new(),
+        up: HashMap::new(),
+        down: HashMap::new(),
+    };
+    for (face, name) in CONTENT_FACES {
+        for connection in json[name].members() {
+            let connection_model = load_model(
+                models,
+                &texture_atlas.asset_report,
+                connection["model"].as_str(),
+                texture_atlas
+                    .get(
+                        connection["texture"]
+                            .as_str()
+                            .or(model["texture"].as_str())
+                            .unwrap(),
+                    )
+                    .clone(),
+                Vec::new(),
+                Vec::new(),
+            );
+            let by_face = connections.by_face_mut(&face);
+            for block in connection["blocks"].members() {
+                if let Some(id) = block.as_u32() {
+                    by_face.insert(id, connection_model.clone());
+                } else if let Some(tag) = block.as_str().and_then(|tag| tag.strip_prefix('#')) {
+                    match tags.get(tag) {
+                        Some(ids) => {
+                            for id in ids {
+                                by_face.insert(*id, connection_model.clone());
+                            }
+                        }
+                        None => println!("unknown block tag {}", tag),
+                    }
+                }
+            }
+        }
+    }
+    connections
+}
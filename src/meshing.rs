@@ -0,0 +1,1265 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use ultraviolet::Vec3;
+
+use crate::{
+    game::{AtlassedTexture, BlockRegistry, BlockRenderType},
+    glwrappers::{self, Vertex, NO_OCCLUSION, NO_TILE},
+    lod::LodSettings,
+    palette::PalettedStorage,
+    tint::ChunkBiomes,
+    util::*,
+    visibility::FaceConnections,
+};
+
+//cpu side of a chunk, workers get Arc snapshots so edits never wait for a mesh
+#[derive(Clone)]
+pub struct ChunkData {
+    pub blocks: PalettedStorage<u32>,
+    pub light: PalettedStorage<u16>,
+}
+impl ChunkData {
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.blocks.get(x, y, z)
+    }
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: u32) {
+        self.blocks.set(x, y, z, block);
+    }
+    pub fn get_light(&self, x: usize, y: usize, z: usize) -> u16 {
+        self.light.get(x, y, z)
+    }
+    pub fn set_light(&mut self, x: usize, y: usize, z: usize, light: u16) {
+        self.light.set(x, y, z, light);
+    }
+    pub fn memory_usage(&self) -> usize {
+        self.blocks.memory_usage() + self.light.memory_usage()
+    }
+}
+#[derive(Clone, Copy)]
+pub struct MeshSettings {
+    pub smooth_lighting: bool,
+    pub missing_neighbors: MissingNeighbors,
+    pub lod: LodSettings,
+}
+//what is assumed past the edge of the loaded world
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MissingNeighbors {
+    Air,
+    Solid,
+}
+impl MissingNeighbors {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "air" => Some(MissingNeighbors::Air),
+            "solid" => Some(MissingNeighbors::Solid),
+            _ => None,
+        }
+    }
+}
+//a chunk and the 26 chunks around it, indexed by neighbor_index, neighbors that aren't loaded
+//are missing, edge and corner neighbors are needed for smooth lighting
+pub struct MeshSnapshot {
+    pub position: ChunkPosition,
+    pub revision: u32,
+    pub chunk: Arc<ChunkData>,
+    pub neighbors: [Option<Arc<ChunkData>>; 27],
+    pub biomes: Option<Arc<ChunkBiomes>>,
+    //0 is full detail, see lod.rs
+    pub lod: u8,
+}
+//a cell of a coarse mesh, Missing is inside a neighbor that isn't loaded
+enum LodCell {
+    Empty,
+    Missing,
+    Filled { block: u32, transparent: bool },
+}
+pub struct ChunkMesh {
+    pub position: ChunkPosition,
+    pub revision: u32,
+    pub vertices: Vec<Vertex>,
+    pub transparent_vertices: Vec<Vertex>,
+    pub foliage_vertices: Vec<Vertex>,
+    pub connections: FaceConnections,
+}
+pub struct MeshWorkers {
+    jobs: Option<mpsc::Sender<MeshSnapshot>>,
+    results: mpsc::Receiver<ChunkMesh>,
+    threads: Vec<JoinHandle<()>>,
+    //set on drop, queued jobs are skipped instead of meshed
+    stop: Arc<AtomicBool>,
+}
+impl MeshWorkers {
+    pub fn new(block_registry: Arc<BlockRegistry>, settings: MeshSettings) -> Self {
+        let (jobs_tx, jobs_rx) = mpsc::channel::<MeshSnapshot>();
+        let (results_tx, results_rx) = mpsc::channel();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let stop = Arc::new(AtomicBool::new(false));
+        //one core stays free for the render thread
+        let count = std::thread::available_parallelism()
+            .map(|count| count.get().saturating_sub(1))
+            .unwrap_or(1)
+            .max(1);
+        let threads = (0..count)
+            .map(|i| {
+                let jobs_rx = jobs_rx.clone();
+                let results_tx = results_tx.clone();
+                let block_registry = block_registry.clone();
+                let stop = stop.clone();
+                std::thread::Builder::new()
+                    .name(format!("mesher {}", i))
+                    .spawn(move || loop {
+                        let job = jobs_rx.lock().unwrap().recv();
+                        let Ok(job) = job else {
+                            break;
+                        };
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if results_tx
+                            .send(job.build(&block_registry, &settings))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    })
+                    .expect("couldnt spawn mesh worker")
+            })
+            .collect();
+        MeshWorkers {
+            jobs: Some(jobs_tx),
+            results: results_rx,
+            threads,
+            stop,
+        }
+    }
+    pub fn submit(&self, snapshot: MeshSnapshot) {
+        let sent = self.jobs.as_ref().map(|jobs| jobs.send(snapshot));
+        if !matches!(sent, Some(Ok(()))) {
+            println!("mesh workers are gone, chunk isnt meshed");
+        }
+    }
+    pub fn finished(&self) -> Vec<ChunkMesh> {
+        self.results.try_iter().collect()
+    }
+}
+impl Drop for MeshWorkers {
+    fn drop(&mut self) {
+        //workers finish the chunk they are on and skip the rest of the queue, closing the
+        //channel wakes up the ones waiting for a job
+        self.stop.store(true, Ordering::Relaxed);
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let name = thread.thread().name().unwrap_or("mesher").to_string();
+            if thread.join().is_err() {
+                println!("{} panicked", name);
+            }
+        }
+    }
+}
+impl MeshSnapshot {
+    //offsets are -1, 0 or 1 on each axis, the middle entry is the chunk itself and stays empty
+    pub fn neighbor_index(x: i32, y: i32, z: i32) -> usize {
+        ((x + 1) * 9 + (y + 1) * 3 + (z + 1)) as usize
+    }
+    //block and light relative to the chunk, None for missing neighbors
+    fn sample(&self, x: i32, y: i32, z: i32) -> Option<(u32, u16)> {
+        let offset = |value: i32| value.div_euclid(16);
+        let data = match (offset(x), offset(y), offset(z)) {
+            (0, 0, 0) => &*self.chunk,
+            (ox, oy, oz) if ox.abs() <= 1 && oy.abs() <= 1 && oz.abs() <= 1 => {
+                self.neighbors[MeshSnapshot::neighbor_index(ox, oy, oz)].as_deref()?
+            }
+            _ => return None,
+        };
+        let (x, y, z) = (
+            x.rem_euclid(16) as usize,
+            y.rem_euclid(16) as usize,
+            z.rem_euclid(16) as usize,
+        );
+        Some((data.get_block(x, y, z), data.get_light(x, y, z)))
+    }
+    //each vertex averages the light of the up to four blocks in front of it, occlusion comes
+    //from the two sides and the corner
+    fn smooth_corners(
+        &self,
+        block_registry: &BlockRegistry,
+        position: BlockPosition,
+        face: Face,
+        vertices: [Vec3; 4],
+    ) -> ([u16; 4], [u8; 4]) {
+        let normal = face.get_offset();
+        let normal = [normal.x, normal.y, normal.z];
+        let front = [
+            position.x + normal[0],
+            position.y + normal[1],
+            position.z + normal[2],
+        ];
+        let corners = vertices.map(|vertex| {
+            let vertex = [vertex.x, vertex.y, vertex.z];
+            let mut sides = [[0; 3]; 2];
+            let mut side = 0;
+            for axis in 0..3 {
+                if normal[axis] == 0 {
+                    sides[side][axis] = if vertex[axis] > 0.5 { 1 } else { -1 };
+                    side += 1;
+                }
+            }
+            let at = |offsets: &[[i32; 3]]| {
+                let mut position = front;
+                for offset in offsets {
+                    for axis in 0..3 {
+                        position[axis] += offset[axis];
+                    }
+                }
+                self.sample(position[0], position[1], position[2])
+            };
+            let samples = [at(&[]), at(&[sides[0]]), at(&[sides[1]]), at(&sides)];
+            let occludes = samples.map(|sample| {
+                sample.map_or(false, |(block, _)| {
+                    block_registry.get_block(block).is_light_blocking()
+                })
+            });
+            let ao = if occludes[1] && occludes[2] {
+                0
+            } else {
+                NO_OCCLUSION - occludes[1..].iter().filter(|occludes| **occludes).count() as u8
+            };
+            //the corner can't be seen past two occluding sides
+            let lights: Vec<u16> = (0..4)
+                .filter(|i| !occludes[*i] && !(*i == 3 && ao == 0))
+                .filter_map(|i| samples[i].map(|(_, light)| light))
+                .collect();
+            let light =
+                average_light(&lights).unwrap_or_else(|| samples[0].map_or(0, |(_, light)| light));
+            (light, ao)
+        });
+        (
+            corners.map(|corner| corner.0),
+            corners.map(|corner| corner.1),
+        )
+    }
+    //light of the non opaque blocks around the block corner closest to a model vertex
+    fn vertex_light(&self, block_registry: &BlockRegistry, position: Vec3, fallback: u16) -> u16 {
+        let corner = [position.x, position.y, position.z].map(|value| value.round() as i32);
+        let mut lights = Vec::with_capacity(8);
+        for dx in -1..=0 {
+            for dy in -1..=0 {
+                for dz in -1..=0 {
+                    if let Some((block, light)) =
+                        self.sample(corner[0] + dx, corner[1] + dy, corner[2] + dz)
+                    {
+                        if !block_registry.get_block(block).is_light_blocking() {
+                            lights.push(light);
+                        }
+                    }
+                }
+            }
+        }
+        average_light(&lights).unwrap_or(fallback)
+    }
+    pub fn build(&self, block_registry: &BlockRegistry, settings: &MeshSettings) -> ChunkMesh {
+        let chunk = &*self.chunk;
+        let connections = FaceConnections::compute(&chunk.blocks.to_array(), |id| {
+            block_registry.get_block(id).is_light_blocking()
+        });
+        if self.lod > 0 {
+            let (vertices, transparent_vertices) = self.build_lod(block_registry, settings);
+            return ChunkMesh {
+                position: self.position,
+                revision: self.revision,
+                vertices,
+                transparent_vertices,
+                foliage_vertices: Vec::new(),
+                connections,
+            };
+        }
+
+        let mut vertices: Vec<glwrappers::Vertex> = Vec::new();
+        let mut transparent_vertices: Vec<glwrappers::Vertex> = Vec::new();
+        let mut foliage_vertices: Vec<glwrappers::Vertex> = Vec::new();
+        let mut greedy_faces = GreedyFaces::new();
+        let biomes = self.biomes.clone().unwrap_or_default();
+        for bx in 0..16i32 {
+            let x = bx as f32;
+            for by in 0..16i32 {
+                let y = by as f32;
+                for bz in 0..16i32 {
+                    let z = bz as f32;
+                    let block_id = chunk.get_block(bx as usize, by as usize, bz as usize);
+                    let block = block_registry.get_block(block_id);
+                    let biome = &biomes[bx as usize][bz as usize];
+                    let position = BlockPosition {
+                        x: bx,
+                        y: by,
+                        z: bz,
+                    };
+                    match &block.render_type {
+                        BlockRenderType::Air => {}
+                        BlockRenderType::Cube(transparent, north, south, right, left, up, down) => {
+                            for face in Face::all() {
+                                let neighbor_pos = position + face.get_offset();
+                                let (neighbor_side_full, light) = match self.sample(
+                                    neighbor_pos.x,
+                                    neighbor_pos.y,
+                                    neighbor_pos.z,
+                                ) {
+                                    Some((neighbor_block, light)) => {
+                                        let neighbor_block =
+                                            block_registry.get_block(neighbor_block);
+                                        (
+                                            neighbor_block.is_face_full(&face.opposite())
+                                                && !(neighbor_block.is_transparent()
+                                                    && !block.is_transparent()),
+                                            light,
+                                        )
+                                    }
+                                    None => match settings.missing_neighbors {
+                                        MissingNeighbors::Air => (false, 15 << 12),
+                                        MissingNeighbors::Solid => (true, 0),
+                                    },
+                                };
+                                let model_face = block.rotation.unrotate_face(*face);
+                                let texture = match model_face {
+                                    Face::Front => north,
+                                    Face::Back => south,
+                                    Face::Right => right,
+                                    Face::Left => left,
+                                    Face::Up => up,
+                                    Face::Down => down,
+                                };
+                                if !neighbor_side_full {
+                                    //with uv lock the texture stays upright and only moves to another face
+                                    let face_vertices = if block.rotation.uv_lock {
+                                        face.get_vertices()
+                                    } else {
+                                        model_face.get_vertices().map(|(position, corner)| {
+                                            (block.rotation.rotate_point(position), corner)
+                                        })
+                                    };
+                                    let animation = texture.animation_id();
+                                    let tint = block.tint.color_for_face(&model_face, biome);
+                                    let (corner_light, ao) = if settings.smooth_lighting {
+                                        self.smooth_corners(
+                                            block_registry,
+                                            position,
+                                            *face,
+                                            face_vertices.map(|(position, _)| position),
+                                        )
+                                    } else {
+                                        ([light; 4], [NO_OCCLUSION; 4])
+                                    };
+                                    //opaque faces are merged once the whole chunk is known,
+                                    //vertex effects need the vertices of every single block
+                                    if !*transparent && block.render_data == 0 {
+                                        greedy_faces.insert(
+                                            *face,
+                                            position,
+                                            GreedyFace::new(
+                                                face,
+                                                &face_vertices,
+                                                texture,
+                                                block.render_data,
+                                                corner_light,
+                                                ao,
+                                                tint,
+                                            ),
+                                        );
+                                        continue;
+                                    }
+                                    let uv = texture.get_coords();
+                                    let vertices = if *transparent {
+                                        &mut transparent_vertices
+                                    } else {
+                                        &mut vertices
+                                    };
+                                    for k in quad_order(&ao) {
+                                        let (u, v) = face_vertices[k].1.map(uv);
+                                        vertices.push(glwrappers::Vertex {
+                                            x: face_vertices[k].0.x + x,
+                                            y: face_vertices[k].0.y + y,
+                                            z: face_vertices[k].0.z + z,
+                                            u,
+                                            v,
+                                            render_data: block.render_data,
+                                            light: corner_light[k],
+                                            animation,
+                                            tint,
+                                            tile: NO_TILE,
+                                            ao: ao[k],
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                        BlockRenderType::StaticModel(
+                            transparent,
+                            model,
+                            _,
+                            _,
+                            _,
+                            _,
+                            _,
+                            _,
+                            connections,
+                            foliage,
+                        ) => {
+                            let tint = block.tint.color(biome);
+                            let original_offset_in_chunk = position.chunk_offset();
+                            let light = chunk.get_light(
+                                original_offset_in_chunk.0 as usize,
+                                original_offset_in_chunk.1 as usize,
+                                original_offset_in_chunk.2 as usize,
+                            );
+                            let vertices = if *transparent {
+                                &mut transparent_vertices
+                            } else if *foliage {
+                                &mut foliage_vertices
+                            } else {
+                                &mut vertices
+                            };
+                            for face in Face::all() {
+                                let neighbor_pos = position + face.get_offset();
+                                let neighbor_block = self
+                                    .sample(neighbor_pos.x, neighbor_pos.y, neighbor_pos.z)
+                                    .map(|(block, _)| block);
+                                if let Some(connection) =
+                                    neighbor_block.and_then(|neighbor_block| {
+                                        connections
+                                            .by_face(&block.rotation.unrotate_face(*face))
+                                            .get(&neighbor_block)
+                                    })
+                                {
+                                    connection.add_vertices_simple(
+                                        &mut |pos, u, v| {
+                                            let pos = block.rotation.rotate_point(Vec3 {
+                                                x: pos.x + 0.5 - x,
+                                                y: pos.y - y,
+                                                z: pos.z + 0.5 - z,
+                                            });
+                                            let pos = Vec3::new(pos.x + x, pos.y + y, pos.z + z);
+                                            vertices.push(Vertex {
+                                                x: pos.x,
+                                                y: pos.y,
+                                                z: pos.z,
+                                                u,
+                                                v,
+                                                render_data: block.render_data,
+                                                light: if settings.smooth_lighting {
+                                                    self.vertex_light(block_registry, pos, light)
+                                                } else {
+                                                    light
+                                                },
+                                                animation: connection.get_texture().animation_id(),
+                                                tint,
+                                                tile: NO_TILE,
+                                                ao: NO_OCCLUSION,
+                                            });
+                                        },
+                                        None,
+                                        Vec3 {
+                                            x: bx as f32,
+                                            y: by as f32,
+                                            z: bz as f32,
+                                        },
+                                        None,
+                                    );
+                                }
+                            }
+                            let animation = model.get_texture().animation_id();
+                            model.add_vertices_simple(
+                                &mut |pos, u, v| {
+                                    let pos = block.rotation.rotate_point(Vec3 {
+                                        x: pos.x + 0.5 - x,
+                                        y: pos.y - y,
+                                        z: pos.z + 0.5 - z,
+                                    });
+                                    let pos = Vec3::new(pos.x + x, pos.y + y, pos.z + z);
+                                    vertices.push(Vertex {
+                                        x: pos.x,
+                                        y: pos.y,
+                                        z: pos.z,
+                                        u,
+                                        v,
+                                        render_data: block.render_data,
+                                        light: if settings.smooth_lighting {
+                                            self.vertex_light(block_registry, pos, light)
+                                        } else {
+                                            light
+                                        },
+                                        animation,
+                                        tint,
+                                        tile: NO_TILE,
+                                        ao: NO_OCCLUSION,
+                                    });
+                                },
+                                None,
+                                Vec3 {
+                                    x: bx as f32,
+                                    y: by as f32,
+                                    z: bz as f32,
+                                },
+                                None,
+                            );
+                        }
+                        BlockRenderType::Foliage(texture1, texture2, texture3, texture4) => {
+                            let original_offset_in_chunk = position.chunk_offset();
+                            let light = chunk.get_light(
+                                original_offset_in_chunk.0 as usize,
+                                original_offset_in_chunk.1 as usize,
+                                original_offset_in_chunk.2 as usize,
+                            );
+                            let tint = block.tint.color(biome);
+                            let mut face_creator =
+                                |p1: Position,
+                                 p2: Position,
+                                 p3: Position,
+                                 p4: Position,
+                                 uv: (f32, f32, f32, f32),
+                                 render_data: u8,
+                                 light: u16,
+                                 animation: u8| {
+                                    let v1 = Vertex {
+                                        x: p1.x,
+                                        y: p1.y,
+                                        z: p1.z,
+                                        u: uv.0,
+                                        v: uv.1,
+                                        render_data,
+                                        light,
+                                        animation,
+                                        tint,
+                                        tile: NO_TILE,
+                                        ao: NO_OCCLUSION,
+                                    };
+                                    let v2 = Vertex {
+                                        x: p2.x,
+                                        y: p2.y,
+                                        z: p2.z,
+                                        u: uv.2,
+                                        v: uv.1,
+                                        render_data,
+                                        light,
+                                        animation,
+                                        tint,
+                                        tile: NO_TILE,
+                                        ao: NO_OCCLUSION,
+                                    };
+                                    let v3 = Vertex {
+                                        x: p3.x,
+                                        y: p3.y,
+                                        z: p3.z,
+                                        u: uv.2,
+                                        v: uv.3,
+                                        render_data,
+                                        light,
+                                        animation,
+                                        tint,
+                                        tile: NO_TILE,
+                                        ao: NO_OCCLUSION,
+                                    };
+                                    let v4 = Vertex {
+                                        x: p4.x,
+                                        y: p4.y,
+                                        z: p4.z,
+                                        u: uv.0,
+                                        v: uv.3,
+                                        render_data,
+                                        light,
+                                        animation,
+                                        tint,
+                                        tile: NO_TILE,
+                                        ao: NO_OCCLUSION,
+                                    };
+                                    foliage_vertices.push(v1);
+                                    foliage_vertices.push(v2);
+                                    foliage_vertices.push(v3);
+                                    foliage_vertices.push(v3);
+                                    foliage_vertices.push(v4);
+                                    foliage_vertices.push(v1);
+                                };
+                            if let Some(texture1) = texture1 {
+                                face_creator.call_mut((
+                                    Position {
+                                        x: x + 0.01,
+                                        y: y + 0.99,
+                                        z,
+                                    },
+                                    Position {
+                                        x: x + 0.99,
+                                        y: y + 0.99,
+                                        z: z + 0.99,
+                                    },
+                                    Position {
+                                        x: x + 0.99,
+                                        y,
+                                        z: z + 0.99,
+                                    },
+                                    Position { x: x + 0.01, y, z },
+                                    texture1.get_coords(),
+                                    block.render_data,
+                                    light,
+                                    texture1.animation_id(),
+                                ));
+                            }
+                            if let Some(texture2) = texture2 {
+                                face_creator.call_mut((
+                                    Position {
+                                        x: x + 0.99,
+                                        y: y + 0.99,
+                                        z,
+                                    },
+                                    Position {
+                                        x: x + 0.01,
+                                        y: y + 0.99,
+                                        z: z + 0.99,
+                                    },
+                                    Position {
+                                        x: x + 0.01,
+                                        y,
+                                        z: z + 1.,
+                                    },
+                                    Position { x: x + 0.99, y, z },
+                                    texture2.get_coords(),
+                                    block.render_data,
+                                    light,
+                                    texture2.animation_id(),
+                                ));
+                            }
+                            if let Some(texture3) = texture3 {
+                                face_creator.call_mut((
+                                    Position {
+                                        x: x + 0.5,
+                                        y: y + 0.99,
+                                        z,
+                                    },
+                                    Position {
+                                        x: x + 0.5,
+                                        y: y + 0.99,
+                                        z: z + 0.99,
+                                    },
+                                    Position {
+                                        x: x + 0.5,
+                                        y,
+                                        z: z + 0.99,
+                                    },
+                                    Position { x: x + 0.5, y, z },
+                                    texture3.get_coords(),
+                                    block.render_data,
+                                    light,
+                                    texture3.animation_id(),
+                                ));
+                            }
+                            if let Some(texture4) = texture4 {
+                                face_creator.call_mut((
+                                    Position {
+                                        x: x + 0.1,
+                                        y: y + 0.99,
+                                        z: z + 0.5,
+                                    },
+                                    Position {
+                                        x: x + 0.99,
+                                        y: y + 0.99,
+                                        z: z + 0.5,
+                                    },
+                                    Position {
+                                        x: x + 0.99,
+                                        y,
+                                        z: z + 0.5,
+                                    },
+                                    Position {
+                                        x: x + 0.01,
+                                        y,
+                                        z: z + 0.5,
+                                    },
+                                    texture4.get_coords(),
+                                    block.render_data,
+                                    light,
+                                    texture4.animation_id(),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        greedy_faces.build(&mut vertices);
+        ChunkMesh {
+            position: self.position,
+            revision: self.revision,
+            vertices,
+            transparent_vertices,
+            foliage_vertices,
+            connections,
+        }
+    }
+    //a cell is filled when most of it is cubes, the most common one stands in for all of it,
+    //opaque ones win over transparent ones and everything that isn't a cube counts as empty
+    fn lod_cell(&self, block_registry: &BlockRegistry, origin: [i32; 3], size: i32) -> LodCell {
+        let mut opaque: Vec<(u32, u32)> = Vec::new();
+        let mut transparent: Vec<(u32, u32)> = Vec::new();
+        for x in origin[0]..origin[0] + size {
+            for y in origin[1]..origin[1] + size {
+                for z in origin[2]..origin[2] + size {
+                    let Some((block, _)) = self.sample(x, y, z) else {
+                        return LodCell::Missing;
+                    };
+                    let counts = match block_registry.get_block(block).render_type {
+                        BlockRenderType::Cube(false, _, _, _, _, _, _) => &mut opaque,
+                        BlockRenderType::Cube(true, _, _, _, _, _, _) => &mut transparent,
+                        _ => continue,
+                    };
+                    match counts.iter_mut().find(|(id, _)| *id == block) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((block, 1)),
+                    }
+                }
+            }
+        }
+        let cubes: u32 = opaque
+            .iter()
+            .chain(transparent.iter())
+            .map(|(_, count)| count)
+            .sum();
+        if cubes * 2 <= (size * size * size) as u32 {
+            return LodCell::Empty;
+        }
+        let most_common = |counts: &Vec<(u32, u32)>| {
+            counts
+                .iter()
+                .max_by_key(|(_, count)| *count)
+                .map(|(block, _)| *block)
+        };
+        if let Some(block) = most_common(&opaque) {
+            LodCell::Filled {
+                block,
+                transparent: false,
+            }
+        } else if let Some(block) = most_common(&transparent) {
+            LodCell::Filled {
+                block,
+                transparent: true,
+            }
+        } else {
+            LodCell::Empty
+        }
+    }
+    fn build_lod(
+        &self,
+        block_registry: &BlockRegistry,
+        settings: &MeshSettings,
+    ) -> (Vec<Vertex>, Vec<Vertex>) {
+        let size = LodSettings::cell_size(self.lod);
+        let biomes = self.biomes.clone().unwrap_or_default();
+        let mut vertices = Vec::new();
+        let mut transparent_vertices = Vec::new();
+        for cx in 0..16 / size {
+            for cy in 0..16 / size {
+                for cz in 0..16 / size {
+                    let origin = [cx * size, cy * size, cz * size];
+                    let LodCell::Filled { block, transparent } =
+                        self.lod_cell(block_registry, origin, size)
+                    else {
+                        continue;
+                    };
+                    let block = block_registry.get_block(block);
+                    let BlockRenderType::Cube(_, north, south, right, left, up, down) =
+                        &block.render_type
+                    else {
+                        continue;
+                    };
+                    for face in Face::all() {
+                        let offset = face.get_offset();
+                        let offset = [offset.x, offset.y, offset.z];
+                        let neighbor = [0, 1, 2].map(|axis| origin[axis] + offset[axis] * size);
+                        let outside = neighbor.iter().any(|value| !(0..16).contains(value));
+                        let hidden = match self.lod_cell(block_registry, neighbor, size) {
+                            LodCell::Missing => {
+                                settings.missing_neighbors == MissingNeighbors::Solid
+                            }
+                            //the neighbor chunk can be meshed at another level and doesnt have
+                            //to draw anything where this cell is
+                            _ if outside => false,
+                            LodCell::Empty => false,
+                            LodCell::Filled {
+                                transparent: neighbor_transparent,
+                                ..
+                            } => !neighbor_transparent || transparent,
+                        };
+                        if hidden {
+                            continue;
+                        }
+                        let model_face = block.rotation.unrotate_face(*face);
+                        let texture = match model_face {
+                            Face::Front => north,
+                            Face::Back => south,
+                            Face::Right => right,
+                            Face::Left => left,
+                            Face::Up => up,
+                            Face::Down => down,
+                        };
+                        let (normal, a, b) = GreedyFaces::axes(*face);
+                        //brightest block in front of the face, cells are mostly lit from one side
+                        let mut light = [0u16; 4];
+                        for i in 0..size {
+                            for j in 0..size {
+                                let mut front = origin;
+                                front[normal] += if offset[normal] > 0 { size } else { -1 };
+                                front[a] += i;
+                                front[b] += j;
+                                let sample = self
+                                    .sample(front[0], front[1], front[2])
+                                    .map_or(15 << 12, |(_, light)| light);
+                                for channel in 0..4 {
+                                    light[channel] =
+                                        light[channel].max((sample >> (channel * 4)) & 15);
+                                }
+                            }
+                        }
+                        let light = light
+                            .iter()
+                            .enumerate()
+                            .fold(0, |packed, (channel, light)| {
+                                packed | (light << (channel * 4))
+                            });
+                        let greedy_face = GreedyFace::new(
+                            face,
+                            &face.get_vertices(),
+                            texture,
+                            0,
+                            [light; 4],
+                            [NO_OCCLUSION; 4],
+                            block.tint.color_for_face(
+                                &model_face,
+                                &biomes[origin[0] as usize][origin[2] as usize],
+                            ),
+                        );
+                        //the quad is placed like a face of the last block of the cell
+                        let mut quad_origin = origin.map(|value| value as f32);
+                        if offset[normal] > 0 {
+                            quad_origin[normal] += (size - 1) as f32;
+                        }
+                        GreedyFaces::add_quad(
+                            if transparent {
+                                &mut transparent_vertices
+                            } else {
+                                &mut vertices
+                            },
+                            face,
+                            &greedy_face,
+                            quad_origin,
+                            (a, size as f32),
+                            (b, size as f32),
+                        );
+                    }
+                }
+            }
+        }
+        (vertices, transparent_vertices)
+    }
+}
+
+//opaque cube faces waiting to be merged, everything that has to match for two faces to share a quad
+#[derive(Clone, Copy, PartialEq)]
+pub struct GreedyFace {
+    texture: AtlassedTexture,
+    //uv corner of each vertex of Face::get_vertices, rotated blocks shuffle these
+    corners: [Corner; 4],
+    render_data: u8,
+    light: [u16; 4],
+    ao: [u8; 4],
+    tint: [u8; 3],
+}
+impl GreedyFace {
+    //light and ao are given per vertex of face_vertices
+    pub fn new(
+        face: &Face,
+        face_vertices: &[(Vec3, Corner); 4],
+        texture: &AtlassedTexture,
+        render_data: u8,
+        light: [u16; 4],
+        ao: [u8; 4],
+        tint: [u8; 3],
+    ) -> Self {
+        let order = face.get_vertices().map(|(position, _)| {
+            face_vertices
+                .iter()
+                .position(|(other, _)| (*other - position).mag_sq() < 0.0001)
+                .unwrap()
+        });
+        GreedyFace {
+            texture: *texture,
+            corners: order.map(|i| face_vertices[i].1),
+            render_data,
+            light: order.map(|i| light[i]),
+            ao: order.map(|i| ao[i]),
+            tint,
+        }
+    }
+    //faces with shading across them only look right on their own
+    fn is_uniform(&self) -> bool {
+        self.light.iter().all(|light| *light == self.light[0])
+            && self.ao.iter().all(|ao| *ao == self.ao[0])
+    }
+}
+//splits a quad along the diagonal with less occlusion, so dark corners don't smear along it
+fn quad_order(ao: &[u8; 4]) -> [usize; 6] {
+    if ao[0] + ao[2] >= ao[1] + ao[3] {
+        [0, 1, 2, 2, 3, 0]
+    } else {
+        [1, 2, 3, 3, 0, 1]
+    }
+}
+fn average_light(lights: &[u16]) -> Option<u16> {
+    if lights.is_empty() {
+        return None;
+    }
+    let count = lights.len() as u16;
+    Some([0, 4, 8, 12].iter().fold(0, |average, shift| {
+        let sum: u16 = lights.iter().map(|light| (light >> shift) & 15).sum();
+        average | (((sum + count / 2) / count) << shift)
+    }))
+}
+pub struct GreedyFaces {
+    faces: Vec<Option<GreedyFace>>,
+}
+impl GreedyFaces {
+    pub fn new() -> Self {
+        GreedyFaces {
+            faces: vec![None; 6 * 16 * 16 * 16],
+        }
+    }
+    //normal axis and the two axes spanning the face
+    fn axes(face: Face) -> (usize, usize, usize) {
+        match face {
+            Face::Up | Face::Down => (1, 0, 2),
+            Face::Front | Face::Back => (2, 0, 1),
+            Face::Left | Face::Right => (0, 2, 1),
+        }
+    }
+    fn index(face: Face, position: [usize; 3]) -> usize {
+        (face as usize * 16 * 16 * 16) + (position[0] * 16 * 16) + (position[1] * 16) + position[2]
+    }
+    pub fn insert(&mut self, face: Face, position: BlockPosition, greedy_face: GreedyFace) {
+        let index = GreedyFaces::index(
+            face,
+            [
+                position.x as usize,
+                position.y as usize,
+                position.z as usize,
+            ],
+        );
+        self.faces[index] = Some(greedy_face);
+    }
+    //grows every face as far as possible along the first axis, then along the second
+    pub fn build(&mut self, vertices: &mut Vec<Vertex>) {
+        for face in Face::all() {
+            let (normal, a, b) = GreedyFaces::axes(*face);
+            for slice in 0..16 {
+                let at = |i: usize, j: usize| {
+                    let mut position = [0; 3];
+                    position[normal] = slice;
+                    position[a] = i;
+                    position[b] = j;
+                    GreedyFaces::index(*face, position)
+                };
+                for j in 0..16 {
+                    let mut i = 0;
+                    while i < 16 {
+                        let Some(current) = self.faces[at(i, j)] else {
+                            i += 1;
+                            continue;
+                        };
+                        let uniform = current.is_uniform();
+                        let mut width = 1;
+                        while uniform
+                            && i + width < 16
+                            && self.faces[at(i + width, j)] == Some(current)
+                        {
+                            width += 1;
+                        }
+                        let mut height = 1;
+                        while uniform
+                            && j + height < 16
+                            && (i..i + width)
+                                .all(|k| self.faces[at(k, j + height)] == Some(current))
+                        {
+                            height += 1;
+                        }
+                        for dj in 0..height {
+                            for di in 0..width {
+                                self.faces[at(i + di, j + dj)] = None;
+                            }
+                        }
+                        let mut origin = [0.; 3];
+                        origin[normal] = slice as f32;
+                        origin[a] = i as f32;
+                        origin[b] = j as f32;
+                        GreedyFaces::add_quad(
+                            vertices,
+                            face,
+                            &current,
+                            origin,
+                            (a, width as f32),
+                            (b, height as f32),
+                        );
+                        i += width;
+                    }
+                }
+            }
+        }
+    }
+    fn add_quad(
+        vertices: &mut Vec<Vertex>,
+        face: &Face,
+        greedy_face: &GreedyFace,
+        origin: [f32; 3],
+        a: (usize, f32),
+        b: (usize, f32),
+    ) {
+        let face_vertices = face.get_vertices();
+        let positions = face_vertices.map(|(position, _)| {
+            let mut position = [position.x, position.y, position.z];
+            position[a.0] *= a.1;
+            position[b.0] *= b.1;
+            [
+                origin[0] + position[0],
+                origin[1] + position[1],
+                origin[2] + position[2],
+            ]
+        });
+        //u runs along whichever axis differs between two corners on the same row of the texture
+        let local = greedy_face
+            .corners
+            .map(|corner| corner.map((0., 0., 1., 1.)));
+        let u_axis = (1..4)
+            .find(|k| local[*k].1 == local[0].1 && local[*k].0 != local[0].0)
+            .map(|k| {
+                if positions[k][a.0] != positions[0][a.0] {
+                    a
+                } else {
+                    b
+                }
+            })
+            .unwrap();
+        let v_extent = if u_axis.0 == a.0 { b.1 } else { a.1 };
+        let texture = &greedy_face.texture;
+        let tile = [
+            texture.x as f32 / texture.atlas_w as f32,
+            texture.y as f32 / texture.atlas_h as f32,
+            texture.w as f32 / texture.atlas_w as f32,
+            texture.h as f32 / texture.atlas_h as f32,
+        ]
+        .map(|value| (value * u16::MAX as f32).round() as u16);
+        for k in quad_order(&greedy_face.ao) {
+            vertices.push(Vertex {
+                x: positions[k][0],
+                y: positions[k][1],
+                z: positions[k][2],
+                u: local[k].0 * u_axis.1,
+                v: local[k].1 * v_extent,
+                render_data: greedy_face.render_data,
+                light: greedy_face.light[k],
+                animation: texture.animation_id(),
+                tint: greedy_face.tint,
+                tile,
+                ao: greedy_face.ao[k],
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(block: impl Fn(usize, usize, usize) -> u32) -> MeshSnapshot {
+        let mut data = ChunkData {
+            blocks: PalettedStorage::uniform(0),
+            light: PalettedStorage::uniform(15 << 12),
+        };
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    data.set_block(x, y, z, block(x, y, z));
+                }
+            }
+        }
+        MeshSnapshot {
+            position: ChunkPosition { x: 0, y: 0, z: 0 },
+            revision: 0,
+            chunk: Arc::new(data),
+            neighbors: Default::default(),
+            biomes: None,
+            lod: 0,
+        }
+    }
+    fn settings() -> MeshSettings {
+        MeshSettings {
+            smooth_lighting: false,
+            missing_neighbors: MissingNeighbors::Air,
+            lod: LodSettings::default(),
+        }
+    }
+    //greedy for plain stone, face by face for the same layout in stone with a vertex effect
+    fn greedy_and_per_face(layout: impl Fn(usize, usize, usize) -> bool) -> (ChunkMesh, ChunkMesh) {
+        let registry = BlockRegistry::for_tests();
+        let mesh = |block: u32| {
+            snapshot(|x, y, z| if layout(x, y, z) { block } else { 0 })
+                .build(&registry, &settings())
+        };
+        (
+            mesh(BlockRegistry::STONE),
+            mesh(BlockRegistry::EFFECT_STONE),
+        )
+    }
+    //summed triangle area by normal axis and direction
+    fn area_per_face(vertices: &[Vertex]) -> [f32; 6] {
+        let mut areas = [0.; 6];
+        for triangle in vertices.chunks(3) {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| Vec3::new(triangle[i].x, triangle[i].y, triangle[i].z));
+            let normal = (b - a).cross(c - a);
+            let normal = [normal.x, normal.y, normal.z];
+            let axis = (0..3)
+                .max_by(|i, j| normal[*i].abs().total_cmp(&normal[*j].abs()))
+                .unwrap();
+            let direction = axis * 2 + (normal[axis] > 0.) as usize;
+            areas[direction] += normal[axis].abs() / 2.;
+        }
+        areas
+    }
+
+    #[test]
+    fn slab_merges_into_one_quad_per_side() {
+        let (greedy, per_face) = greedy_and_per_face(|_, y, _| y == 0);
+        //top and bottom are 256 faces each, every side 16
+        assert_eq!(per_face.vertices.len(), (256 * 2 + 16 * 4) * 6);
+        assert_eq!(greedy.vertices.len(), 6 * 6);
+        assert!(greedy.vertices.len() < per_face.vertices.len());
+        assert_eq!(
+            area_per_face(&greedy.vertices),
+            area_per_face(&per_face.vertices)
+        );
+    }
+
+    #[test]
+    fn checkerboard_covers_same_area() {
+        let (greedy, per_face) = greedy_and_per_face(|x, y, z| y == 0 && (x + z) % 2 == 0);
+        //no two faces touch, so there is nothing to merge
+        assert_eq!(per_face.vertices.len(), 128 * 6 * 6);
+        assert!(greedy.vertices.len() <= per_face.vertices.len());
+        assert_eq!(
+            area_per_face(&greedy.vertices),
+            area_per_face(&per_face.vertices)
+        );
+    }
+
+    #[test]
+    fn faces_with_different_light_are_not_merged() {
+        let registry = BlockRegistry::for_tests();
+        let mut snapshot = snapshot(|_, y, _| if y == 0 { BlockRegistry::STONE } else { 0 });
+        let chunk = Arc::make_mut(&mut snapshot.chunk);
+        chunk.set_light(3, 1, 3, 7);
+        let mesh = snapshot.build(&registry, &settings());
+        let lit: Vec<_> = mesh
+            .vertices
+            .iter()
+            .filter(|vertex| vertex.light == 7)
+            .collect();
+        //the one darker top face stays a single block quad
+        assert_eq!(lit.len(), 6);
+        assert!(lit.iter().all(|vertex| {
+            let (x, z) = (vertex.x, vertex.z);
+            (3. ..=4.).contains(&x) && (3. ..=4.).contains(&z)
+        }));
+        assert_eq!(
+            area_per_face(&mesh.vertices),
+            area_per_face(&greedy_and_per_face(|_, y, _| y == 0).1.vertices)
+        );
+    }
+
+    #[test]
+    fn corner_samples_read_diagonal_neighbors() {
+        let registry = BlockRegistry::for_tests();
+        let mut snapshot = snapshot(|x, y, z| {
+            if (x, y, z) == (15, 0, 15) {
+                BlockRegistry::STONE
+            } else {
+                0
+            }
+        });
+        let corner = |snapshot: &MeshSnapshot| {
+            let position = BlockPosition { x: 15, y: 0, z: 15 };
+            let vertices = [Vec3::new(1., 1., 1.); 4];
+            snapshot
+                .smooth_corners(&registry, position, Face::Up, vertices)
+                .1[0]
+        };
+        assert_eq!(snapshot.sample(16, 1, 16), None);
+        assert_eq!(corner(&snapshot), NO_OCCLUSION);
+        let diagonal = self::snapshot(|x, y, z| {
+            if (x, y, z) == (0, 1, 0) {
+                BlockRegistry::STONE
+            } else {
+                0
+            }
+        });
+        snapshot.neighbors[MeshSnapshot::neighbor_index(1, 0, 1)] = Some(diagonal.chunk);
+        assert_eq!(
+            snapshot.sample(16, 1, 16).map(|(block, _)| block),
+            Some(BlockRegistry::STONE)
+        );
+        assert_eq!(corner(&snapshot), NO_OCCLUSION - 1);
+    }
+
+    #[test]
+    fn lod_cells_need_mostly_cubes() {
+        let registry = BlockRegistry::for_tests();
+        let mut single = snapshot(|x, y, z| {
+            if (x, y, z) == (0, 0, 0) {
+                BlockRegistry::STONE
+            } else {
+                0
+            }
+        });
+        single.lod = 2;
+        assert!(single.build(&registry, &settings()).vertices.is_empty());
+        //six of the eight blocks of the first level 1 cell
+        let mut most = snapshot(|x, y, z| {
+            if x < 2 && y < 2 && z < 2 && (x, y, z) != (1, 1, 1) && (x, y, z) != (0, 1, 1) {
+                BlockRegistry::STONE
+            } else {
+                0
+            }
+        });
+        most.lod = 1;
+        let vertices = most.build(&registry, &settings()).vertices;
+        assert_eq!(area_per_face(&vertices), [4.; 6]);
+    }
+
+    #[test]
+    fn lod_faces_against_other_chunks_are_drawn() {
+        let registry = BlockRegistry::for_tests();
+        let mut snapshot = snapshot(|_, _, _| BlockRegistry::STONE);
+        snapshot.lod = 1;
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if (x, y, z) != (0, 0, 0) {
+                        snapshot.neighbors[MeshSnapshot::neighbor_index(x, y, z)] =
+                            Some(snapshot.chunk.clone());
+                    }
+                }
+            }
+        }
+        //the neighbors might be meshed at full detail and leave gaps where these cells are
+        let vertices = snapshot.build(&registry, &settings()).vertices;
+        assert_eq!(area_per_face(&vertices), [256.; 6]);
+    }
+}
@@ -0,0 +1,237 @@
+use image::{Rgba, RgbaImage};
+
+const NO_TILE: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MipTile {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+impl MipTile {
+    pub fn with_extrusion(x: u32, y: u32, w: u32, h: u32, extrusion: u32) -> MipTile {
+        MipTile {
+            x: x.saturating_sub(extrusion),
+            y: y.saturating_sub(extrusion),
+            w: w + extrusion * 2,
+            h: h + extrusion * 2,
+        }
+    }
+}
+
+//number of levels including the base one, so that the smallest tile still covers at least one pixel
+pub fn max_mip_levels(tile_sizes: impl Iterator<Item = (u32, u32)>, limit: u32) -> u32 {
+    let smallest = tile_sizes.map(|(w, h)| w.min(h)).min().unwrap_or(1).max(1);
+    (smallest.ilog2() + 1).min(limit.max(1))
+}
+
+//builds the mip chain with a 2x2 box filter, where every pixel only ever averages pixels belonging to the same
+//atlas tile, so neighbouring textures never bleed into each other
+pub fn generate_mip_chain(base: &RgbaImage, tiles: &[MipTile], levels: u32) -> Vec<RgbaImage> {
+    let mut owners = vec![NO_TILE; (base.width() * base.height()) as usize];
+    for (i, tile) in tiles.iter().enumerate() {
+        for y in tile.y..(tile.y + tile.h).min(base.height()) {
+            for x in tile.x..(tile.x + tile.w).min(base.width()) {
+                owners[(x + y * base.width()) as usize] = i as u32;
+            }
+        }
+    }
+    let mut chain = vec![base.clone()];
+    for _ in 1..levels {
+        let source = chain.last().unwrap();
+        if source.width() == 1 && source.height() == 1 {
+            break;
+        }
+        let (level, level_owners) = downsample(source, &owners);
+        owners = level_owners;
+        chain.push(level);
+    }
+    chain
+}
+
+fn downsample(source: &RgbaImage, owners: &[u32]) -> (RgbaImage, Vec<u32>) {
+    let (source_w, source_h) = (source.width(), source.height());
+    let (w, h) = ((source_w / 2).max(1), (source_h / 2).max(1));
+    let mut level = RgbaImage::new(w, h);
+    let mut level_owners = vec![NO_TILE; (w * h) as usize];
+    for y in 0..h {
+        for x in 0..w {
+            let samples = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(ox, oy)| {
+                let sx = (x * 2 + ox).min(source_w - 1);
+                let sy = (y * 2 + oy).min(source_h - 1);
+                (sx, sy, owners[(sx + sy * source_w) as usize])
+            });
+            let owner = samples
+                .iter()
+                .map(|sample| sample.2)
+                .find(|owner| *owner != NO_TILE)
+                .unwrap_or(NO_TILE);
+            let mut color = [0f32; 3];
+            let mut alpha = 0f32;
+            let mut count = 0f32;
+            for (sx, sy, _) in samples.iter().filter(|sample| sample.2 == owner) {
+                let pixel = source.get_pixel(*sx, *sy).0;
+                let a = pixel[3] as f32;
+                for c in 0..3 {
+                    color[c] += pixel[c] as f32 * a;
+                }
+                alpha += a;
+                count += 1.;
+            }
+            let pixel = if alpha > 0. {
+                Rgba([
+                    (color[0] / alpha).round() as u8,
+                    (color[1] / alpha).round() as u8,
+                    (color[2] / alpha).round() as u8,
+                    (alpha / count).round() as u8,
+                ])
+            } else {
+                Rgba([0, 0, 0, 0])
+            };
+            level.put_pixel(x, y, pixel);
+            level_owners[(x + y * w) as usize] = owner;
+        }
+    }
+    (level, level_owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    fn fill(image: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>) {
+        for py in y..y + h {
+            for px in x..x + w {
+                image.put_pixel(px, py, color);
+            }
+        }
+    }
+
+    #[test]
+    fn box_filter_averages_2x2() {
+        let mut image = RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, Rgba([200, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([0, 100, 0, 255]));
+        image.put_pixel(0, 1, Rgba([0, 0, 40, 255]));
+        image.put_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        let chain = generate_mip_chain(
+            &image,
+            &[MipTile {
+                x: 0,
+                y: 0,
+                w: 2,
+                h: 2,
+            }],
+            2,
+        );
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[1].dimensions(), (1, 1));
+        assert_eq!(*chain[1].get_pixel(0, 0), Rgba([50, 25, 10, 255]));
+    }
+
+    #[test]
+    fn box_filter_weights_color_by_alpha() {
+        let mut image = RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, RED);
+        image.put_pixel(1, 0, Rgba([0, 255, 0, 0]));
+        image.put_pixel(0, 1, Rgba([0, 255, 0, 0]));
+        image.put_pixel(1, 1, Rgba([0, 255, 0, 0]));
+        let chain = generate_mip_chain(
+            &image,
+            &[MipTile {
+                x: 0,
+                y: 0,
+                w: 2,
+                h: 2,
+            }],
+            2,
+        );
+        assert_eq!(*chain[1].get_pixel(0, 0), Rgba([255, 0, 0, 64]));
+    }
+
+    #[test]
+    fn filtering_never_mixes_tiles() {
+        //the 2x2 block at x=2 straddles both tiles
+        let mut image = RgbaImage::new(4, 2);
+        fill(&mut image, 0, 0, 3, 2, RED);
+        fill(&mut image, 3, 0, 1, 2, BLUE);
+        let tiles = [
+            MipTile {
+                x: 0,
+                y: 0,
+                w: 3,
+                h: 2,
+            },
+            MipTile {
+                x: 3,
+                y: 0,
+                w: 1,
+                h: 2,
+            },
+        ];
+        let chain = generate_mip_chain(&image, &tiles, 2);
+        assert_eq!(*chain[1].get_pixel(0, 0), RED);
+        assert_eq!(*chain[1].get_pixel(1, 0), RED);
+    }
+
+    #[test]
+    fn padding_does_not_fade_tile_edges() {
+        let mut image = RgbaImage::new(4, 4);
+        fill(&mut image, 1, 1, 2, 2, RED);
+        let chain = generate_mip_chain(
+            &image,
+            &[MipTile {
+                x: 1,
+                y: 1,
+                w: 2,
+                h: 2,
+            }],
+            2,
+        );
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_eq!(*chain[1].get_pixel(x, y), RED);
+            }
+        }
+    }
+
+    #[test]
+    fn extrusion_grows_tile_on_every_side() {
+        let tile = MipTile::with_extrusion(4, 6, 8, 16, 2);
+        assert_eq!((tile.x, tile.y, tile.w, tile.h), (2, 4, 12, 20));
+        let tile = MipTile::with_extrusion(0, 1, 8, 8, 2);
+        assert_eq!((tile.x, tile.y), (0, 0));
+    }
+
+    #[test]
+    fn extruded_pixels_belong_to_their_tile() {
+        //two 2x2 tiles extruded by one pixel, so the extrusions meet in the middle of the atlas
+        let mut image = RgbaImage::new(8, 4);
+        fill(&mut image, 0, 0, 4, 4, RED);
+        fill(&mut image, 4, 0, 4, 4, BLUE);
+        let tiles = [
+            MipTile::with_extrusion(1, 1, 2, 2, 1),
+            MipTile::with_extrusion(5, 1, 2, 2, 1),
+        ];
+        let chain = generate_mip_chain(&image, &tiles, 3);
+        assert_eq!(chain.len(), 3);
+        for y in 0..2 {
+            assert_eq!(*chain[1].get_pixel(0, y), RED);
+            assert_eq!(*chain[1].get_pixel(1, y), RED);
+            assert_eq!(*chain[1].get_pixel(2, y), BLUE);
+            assert_eq!(*chain[1].get_pixel(3, y), BLUE);
+        }
+        assert_eq!(*chain[2].get_pixel(0, 0), RED);
+        assert_eq!(*chain[2].get_pixel(1, 0), BLUE);
+    }
+
+    #[test]
+    fn mip_levels_stop_at_smallest_tile() {
+        assert_eq!(max_mip_levels([(16, 16), (4, 8)].into_iter(), 10), 3);
+        assert_eq!(max_mip_levels([(16, 16)].into_iter(), 2), 2);
+    }
+}
@@ -1,4 +1,3 @@
-use endio::LERead;
 use endio::LEWrite;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
@@ -8,11 +7,19 @@ use ultraviolet::Vec2;
 use ultraviolet::Vec3;
 use ultraviolet::Vec4;
 
+use crate::bbm::BbmAnimationData;
+use crate::bbm::BbmBone;
+use crate::bbm::BbmCube;
+use crate::bbm::BbmFace;
+use crate::bbm::BbmKeyframe;
+use crate::bbm::BbmModel;
+use crate::bbm::ModelError;
 use crate::game::AtlassedTexture;
 use crate::game::BlockRegistry;
 use crate::glwrappers::Vertex;
 use crate::util;
 use crate::util::Corner;
+use crate::util::ItemContext;
 use crate::util::ItemRenderData;
 use crate::util::ItemSlot;
 use crate::util::Position;
@@ -31,9 +38,9 @@ impl Model {
         texture: AtlassedTexture,
         animation_mapping: Vec<String>,
         item_mapping: Vec<String>,
-    ) -> Self {
+    ) -> Result<Self, ModelError> {
         Model::new(
-            std::fs::read(file).unwrap(),
+            std::fs::read(file).map_err(|_| ModelError::Truncated)?,
             texture,
             animation_mapping,
             item_mapping,
@@ -44,20 +51,43 @@ impl Model {
         texture: AtlassedTexture,
         animation_mapping: Vec<String>,
         item_mapping: Vec<String>,
+    ) -> Result<Self, ModelError> {
+        Ok(Model::from_bbm(
+            BbmModel::from_data(data.as_slice())?,
+            texture,
+            animation_mapping,
+            item_mapping,
+        ))
+    }
+    //models written before the bbm header existed
+    pub fn new_legacy(
+        data: Vec<u8>,
+        texture: AtlassedTexture,
+        animation_mapping: Vec<String>,
+        item_mapping: Vec<String>,
+    ) -> Result<Self, ModelError> {
+        Ok(Model::from_bbm(
+            BbmModel::from_legacy_data(data.as_slice())?,
+            texture,
+            animation_mapping,
+            item_mapping,
+        ))
+    }
+    fn from_bbm(
+        model: BbmModel,
+        texture: AtlassedTexture,
+        animation_mapping: Vec<String>,
+        item_mapping: Vec<String>,
     ) -> Self {
-        let mut data = data.as_slice();
-        let root_bone = Bone::from_stream(&mut data, &item_mapping);
-        let animations = {
-            let animations_cnt: u32 = data.read_be().unwrap();
-            let mut animations = Vec::with_capacity(animations_cnt as usize);
-            for _ in 0..animations_cnt {
-                animations.push(Animation {
-                    name: util::read_string(&mut data),
-                    length: data.read_be().unwrap(),
-                })
-            }
-            animations
-        };
+        let root_bone = Bone::from_bbm(model.root_bone, &item_mapping);
+        let animations: Vec<Animation> = model
+            .animations
+            .into_iter()
+            .map(|animation| Animation {
+                name: animation.name,
+                length: animation.length,
+            })
+            .collect();
         let animation_mapping = {
             let mut mapping = Vec::new();
             for animation in animation_mapping {
@@ -86,7 +116,7 @@ impl Model {
         rotation: Vec3,
         rotation_origin: Vec3,
         scale: Vec3,
-        item_rendering: Option<(&HashMap<u32, ItemSlot>, &ItemRenderer)>,
+        item_rendering: Option<(&HashMap<u32, ItemSlot>, &ItemRenderer, ItemContext)>,
     ) where
         F: FnMut(Vec3, f32, f32),
     {
@@ -110,12 +140,23 @@ impl Model {
             item_rendering,
         );
     }
+    //unanimated and without items, used for item models
+    pub fn add_vertices_static<F>(&self, vertex_consumer: &mut F, matrix: Mat4)
+    where
+        F: FnMut(Vec3, f32, f32),
+    {
+        self.root_bone
+            .add_vertices(vertex_consumer, None, matrix, &self.texture, None);
+    }
+    pub fn get_texture(&self) -> &AtlassedTexture {
+        &self.texture
+    }
     pub fn add_vertices_simple<F>(
         &self,
         vertex_consumer: &mut F,
         animation: Option<(String, f32)>,
         position: Vec3,
-        item_rendering: Option<(&HashMap<u32, ItemSlot>, &ItemRenderer)>,
+        item_rendering: Option<(&HashMap<u32, ItemSlot>, &ItemRenderer, ItemContext)>,
     ) where
         F: FnMut(Vec3, f32, f32),
     {
@@ -149,55 +190,41 @@ struct Bone {
     item_mapping: Vec<(u32, ItemElement)>,
 }
 impl Bone {
-    pub fn from_stream(data: &mut &[u8], item_mapping: &Vec<String>) -> Self {
-        let name = util::read_string(data);
-        let origin = from_stream_to_vec3(data);
-        let child_bones = {
-            let child_bones_cnt: u32 = data.read_be().unwrap();
-            let mut child_bones = Vec::with_capacity(child_bones_cnt as usize);
-            for _ in 0..child_bones_cnt {
-                child_bones.push(Bone::from_stream(data, item_mapping));
-            }
-            child_bones
-        };
-        let cube_elements = {
-            let cube_elements_cnt: u32 = data.read_be().unwrap();
-            let mut cube_elements = Vec::with_capacity(cube_elements_cnt as usize);
-            for _ in 0..cube_elements_cnt {
-                cube_elements.push(CubeElement::from_stream(data));
-            }
-            cube_elements
-        };
-        let item_mapping = {
-            let item_elements_cnt: u32 = data.read_be().unwrap();
-            let mut items = Vec::new();
-            for _ in 0..item_elements_cnt {
-                let name = util::read_string(data);
-                let item_element = ItemElement::from_stream(data);
-                for (index, item) in item_mapping.iter().enumerate() {
-                    if item == &name {
-                        items.push((index as u32, item_element));
-                        break;
-                    }
-                }
-            }
-            items
-        };
-        let animations = {
-            let mut animation = BTreeMap::new();
-            let animations_cnt: u32 = data.read_be().unwrap();
-            for _ in 0..animations_cnt {
-                animation.insert(data.read_be().unwrap(), AnimationData::from_stream(data));
-            }
-            animation
-        };
+    fn from_bbm(bone: BbmBone, item_mapping: &[String]) -> Self {
         Bone {
-            item_mapping,
-            name,
-            origin,
-            child_bones,
-            cube_elements,
-            animations,
+            item_mapping: bone
+                .item_elements
+                .into_iter()
+                .filter_map(|item| {
+                    let index = item_mapping.iter().position(|name| name == &item.name)?;
+                    Some((
+                        index as u32,
+                        ItemElement {
+                            position: item.position.into(),
+                            rotation: item.rotation.into(),
+                            origin: item.origin.into(),
+                            size: item.size.into(),
+                        },
+                    ))
+                })
+                .collect(),
+            name: bone.name,
+            origin: bone.origin.into(),
+            child_bones: bone
+                .child_bones
+                .into_iter()
+                .map(|child| Bone::from_bbm(child, item_mapping))
+                .collect(),
+            cube_elements: bone
+                .cube_elements
+                .into_iter()
+                .map(CubeElement::from_bbm)
+                .collect(),
+            animations: bone
+                .animations
+                .into_iter()
+                .map(|(id, animation)| (id, AnimationData::from_bbm(animation)))
+                .collect(),
         }
     }
     pub fn add_vertices<F>(
@@ -206,7 +233,7 @@ impl Bone {
         animation: Option<(u32, f32)>,
         parent_matrix: Mat4,
         texture: &AtlassedTexture,
-        item_rendering: Option<(&HashMap<u32, ItemSlot>, &ItemRenderer)>,
+        item_rendering: Option<(&HashMap<u32, ItemSlot>, &ItemRenderer, ItemContext)>,
     ) where
         F: FnMut(Vec3, f32, f32),
     {
@@ -261,6 +288,7 @@ impl Bone {
                             )),
                         &id.1.position,
                         &id.1.size,
+                        item_rendering.2,
                     );
                 }
             }
@@ -567,26 +595,24 @@ struct CubeElement {
     down: CubeElementFace,
 }
 impl CubeElement {
-    pub fn from_stream(data: &mut &[u8]) -> Self {
-        Self {
-            position: from_stream_to_vec3(data),
-            scale: from_stream_to_vec3(data),
-            rotation: from_stream_to_vec3(data),
-            origin: from_stream_to_vec3(data),
-            front: CubeElement::face_from_stream(data),
-            back: CubeElement::face_from_stream(data),
-            left: CubeElement::face_from_stream(data),
-            right: CubeElement::face_from_stream(data),
-            up: CubeElement::face_from_stream(data),
-            down: CubeElement::face_from_stream(data),
-        }
-    }
-    fn face_from_stream(data: &mut &[u8]) -> CubeElementFace {
-        CubeElementFace {
-            u1: data.read_be().unwrap(),
-            v1: data.read_be().unwrap(),
-            u2: data.read_be().unwrap(),
-            v2: data.read_be().unwrap(),
+    fn from_bbm(cube: BbmCube) -> Self {
+        let face = |face: BbmFace| CubeElementFace {
+            u1: face.u1,
+            v1: face.v1,
+            u2: face.u2,
+            v2: face.v2,
+        };
+        CubeElement {
+            position: cube.position.into(),
+            rotation: cube.rotation.into(),
+            scale: cube.scale.into(),
+            origin: cube.origin.into(),
+            front: face(cube.front),
+            back: face(cube.back),
+            left: face(cube.left),
+            right: face(cube.right),
+            up: face(cube.up),
+            down: face(cube.down),
         }
     }
 }
@@ -604,16 +630,6 @@ struct ItemElement {
     origin: Vec3,
     size: Vec2,
 }
-impl ItemElement {
-    pub fn from_stream(data: &mut &[u8]) -> Self {
-        Self {
-            position: from_stream_to_vec3(data),
-            rotation: from_stream_to_vec3(data),
-            origin: from_stream_to_vec3(data),
-            size: from_stream_to_vec2(data),
-        }
-    }
-}
 #[derive(Clone, Debug)]
 struct AnimationData {
     position: Vec<AnimationKeyframe>,
@@ -621,23 +637,21 @@ struct AnimationData {
     scale: Vec<AnimationKeyframe>,
 }
 impl AnimationData {
-    pub fn from_stream(data: &mut &[u8]) -> Self {
-        Self {
-            position: AnimationData::animation_keyframes_from_stream(data),
-            rotation: AnimationData::animation_keyframes_from_stream(data),
-            scale: AnimationData::animation_keyframes_from_stream(data),
-        }
-    }
-    fn animation_keyframes_from_stream(data: &mut &[u8]) -> Vec<AnimationKeyframe> {
-        let size: u32 = data.read_be().unwrap();
-        let mut keyframes = Vec::with_capacity(size as usize);
-        for _ in 0..size {
-            keyframes.push(AnimationKeyframe {
-                data: from_stream_to_vec3(data),
-                time: data.read_be().unwrap(),
-            })
+    fn from_bbm(animation: BbmAnimationData) -> Self {
+        let keyframes = |keyframes: Vec<BbmKeyframe>| {
+            keyframes
+                .into_iter()
+                .map(|keyframe| AnimationKeyframe {
+                    data: keyframe.data.into(),
+                    time: keyframe.time,
+                })
+                .collect()
+        };
+        AnimationData {
+            position: keyframes(animation.position),
+            rotation: keyframes(animation.rotation),
+            scale: keyframes(animation.scale),
         }
-        keyframes
     }
     pub fn get_for_time(&self, time: f32) -> (Vec3, Vec3, Vec3) {
         (
@@ -692,19 +706,6 @@ struct AnimationKeyframe {
     data: Vec3,
     time: f32,
 }
-fn from_stream_to_vec3(data: &mut &[u8]) -> Vec3 {
-    Vec3 {
-        x: data.read_be().unwrap(),
-        y: data.read_be().unwrap(),
-        z: data.read_be().unwrap(),
-    }
-}
-fn from_stream_to_vec2(data: &mut &[u8]) -> Vec2 {
-    Vec2 {
-        x: data.read_be().unwrap(),
-        y: data.read_be().unwrap(),
-    }
-}
 #[derive(Clone)]
 struct Animation {
     name: String,
@@ -724,6 +725,7 @@ impl<'a> ItemRenderer<'a> {
         matrix: &Mat4,
         position: &Vec3,
         scale: &Vec2,
+        context: ItemContext,
     ) where
         F: FnMut(Vec3, f32, f32),
     {
@@ -1057,6 +1059,174 @@ impl<'a> ItemRenderer<'a> {
                     }
                 }
             }
+            util::ItemModel::Model(model) => {
+                //models are a block wide, fitted to the width of the item element
+                let matrix = *matrix
+                    * Mat4::from_translation(*position + Vec3::new(scale.x / 2., 0., 0.))
+                    * Mat4::from_nonuniform_scale(Vec3::new(scale.x, scale.y, scale.x));
+                //the first person transform is already part of the matrix passed in
+                let matrix = match context {
+                    ItemContext::FirstPerson => matrix,
+                    context => matrix * model.transform(context).matrix(),
+                };
+                model.model.add_vertices_static(vertex_consumer, matrix);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbm;
+    use crate::bbm::BbmAnimation;
+    use crate::bbm::BbmItem;
+
+    fn sample_model() -> BbmModel {
+        let face = BbmFace {
+            u1: 0.,
+            v1: 0.25,
+            u2: 0.5,
+            v2: 0.75,
+        };
+        BbmModel {
+            root_bone: BbmBone {
+                name: String::from("root"),
+                child_bones: vec![BbmBone {
+                    name: String::from("arm"),
+                    origin: [0.25, 1.5, 0.],
+                    cube_elements: vec![BbmCube {
+                        position: [-0.125, 0.5, -0.125],
+                        scale: [0.25, 1., 0.25],
+                        rotation: [0., 0.5, 0.],
+                        origin: [0., 1., 0.],
+                        front: face,
+                        back: face,
+                        left: face,
+                        right: face,
+                        up: face,
+                        down: face,
+                    }],
+                    item_elements: vec![
+                        BbmItem {
+                            name: String::from("hand"),
+                            position: [0., 0.5, 0.],
+                            rotation: [0., 0., 1.],
+                            origin: [0., 0.5, 0.],
+                            size: [0.5, 0.5],
+                        },
+                        BbmItem {
+                            name: String::from("unmapped"),
+                            ..Default::default()
+                        },
+                    ],
+                    animations: vec![(
+                        1,
+                        BbmAnimationData {
+                            rotation: vec![
+                                BbmKeyframe {
+                                    data: [0., 0., 0.],
+                                    time: 0.,
+                                },
+                                BbmKeyframe {
+                                    data: [1., 0., 0.],
+                                    time: 1.,
+                                },
+                            ],
+                            ..Default::default()
+                        },
+                    )],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            animations: vec![
+                BbmAnimation {
+                    name: String::from("idle"),
+                    length: 2.,
+                },
+                BbmAnimation {
+                    name: String::from("walk"),
+                    length: 1.,
+                },
+            ],
         }
     }
+    fn parse(data: Vec<u8>) -> Result<Model, ModelError> {
+        Model::new(
+            data,
+            AtlassedTexture::empty(),
+            vec![String::from("walk")],
+            vec![String::from("offhand"), String::from("hand")],
+        )
+    }
+
+    #[test]
+    fn written_model_parses_in_client() {
+        let model = parse(sample_model().to_data()).unwrap();
+        assert_eq!(model.root_bone.name, "root");
+        assert_eq!(model.animation_mapping, vec![1]);
+        let arm = &model.root_bone.child_bones[0];
+        assert_eq!(arm.name, "arm");
+        assert_eq!(arm.origin, Vec3::new(0.25, 1.5, 0.));
+        assert_eq!(arm.cube_elements.len(), 1);
+        let cube = &arm.cube_elements[0];
+        assert_eq!(cube.position, Vec3::new(-0.125, 0.5, -0.125));
+        assert_eq!(cube.scale, Vec3::new(0.25, 1., 0.25));
+        assert_eq!(cube.rotation, Vec3::new(0., 0.5, 0.));
+        assert_eq!((cube.down.v1, cube.down.u2), (0.25, 0.5));
+        //items the client doesnt know are dropped
+        assert_eq!(arm.item_mapping.len(), 1);
+        let (index, item) = &arm.item_mapping[0];
+        assert_eq!(*index, 1);
+        assert_eq!(item.rotation, Vec3::new(0., 0., 1.));
+        assert_eq!(item.size, Vec2::new(0.5, 0.5));
+        let animation = &arm.animations[&1];
+        assert_eq!(animation.rotation.len(), 2);
+        assert_eq!(animation.get_for_time(0.5).1, Vec3::new(0.5, 0., 0.));
+    }
+
+    #[test]
+    fn written_model_encodes_back_to_same_data() {
+        let data = sample_model().to_data();
+        let parsed = BbmModel::from_data(data.as_slice()).unwrap();
+        assert_eq!(parsed, sample_model());
+        assert_eq!(parsed.to_data(), data);
+    }
+
+    #[test]
+    fn bundled_missing_model_parses() {
+        parse(include_bytes!("missing.bbm").to_vec()).unwrap();
+    }
+
+    #[test]
+    fn headerless_models_parse_as_legacy() {
+        let data = sample_model().to_data();
+        let legacy = data[bbm::MAGIC.len() + 2..].to_vec();
+        assert!(matches!(parse(legacy.clone()), Err(ModelError::BadMagic)));
+        let model = Model::new_legacy(
+            legacy,
+            AtlassedTexture::empty(),
+            vec![String::from("walk")],
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(model.root_bone.child_bones[0].name, "arm");
+        assert_eq!(model.animation_mapping, vec![1]);
+    }
+
+    #[test]
+    fn broken_models_are_rejected() {
+        let data = sample_model().to_data();
+        assert!(matches!(
+            parse(data[..data.len() - 3].to_vec()),
+            Err(ModelError::Truncated)
+        ));
+        let mut newer = data.clone();
+        newer[bbm::MAGIC.len() + 1] += 1;
+        assert!(matches!(
+            parse(newer),
+            Err(ModelError::UnsupportedVersion(version)) if version == bbm::VERSION + 1
+        ));
+    }
 }
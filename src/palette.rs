@@ -0,0 +1,259 @@
+//the 16x16x16 values of a chunk as indices into a palette of the distinct values, packed with
+//as few bits as the palette needs, a chunk of a single value stores no indices at all
+#[derive(Clone)]
+pub struct PalettedStorage<T> {
+    palette: Vec<T>,
+    //how many of the values point at each palette entry
+    counts: Vec<u16>,
+    //0 while there is only one value
+    bits: u8,
+    data: Vec<u64>,
+}
+impl<T: Copy + PartialEq> PalettedStorage<T> {
+    pub fn uniform(value: T) -> Self {
+        PalettedStorage {
+            palette: vec![value],
+            counts: vec![4096],
+            bits: 0,
+            data: Vec::new(),
+        }
+    }
+    pub fn from_array(values: &[[[T; 16]; 16]; 16]) -> Self {
+        let mut storage = PalettedStorage::uniform(values[0][0][0]);
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    storage.set(x, y, z, values[x][y][z]);
+                }
+            }
+        }
+        storage
+    }
+    pub fn to_array(&self) -> [[[T; 16]; 16]; 16] {
+        let mut values = [[[self.palette[0]; 16]; 16]; 16];
+        if self.bits > 0 {
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        values[x][y][z] = self.get(x, y, z);
+                    }
+                }
+            }
+        }
+        values
+    }
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        (x << 8) | (y << 4) | z
+    }
+    fn words(bits: u8) -> usize {
+        4096usize.div_ceil(64 / bits as usize)
+    }
+    //indices never cross a word, some bits at the end of each word stay unused
+    fn location(&self, index: usize) -> (usize, u32) {
+        let per_word = 64 / self.bits as usize;
+        (
+            index / per_word,
+            ((index % per_word) * self.bits as usize) as u32,
+        )
+    }
+    fn get_index(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let (word, shift) = self.location(index);
+        ((self.data[word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+    fn set_index(&mut self, index: usize, palette_index: usize) {
+        let (word, shift) = self.location(index);
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        self.data[word] = (self.data[word] & !mask) | ((palette_index as u64) << shift);
+    }
+    pub fn get(&self, x: usize, y: usize, z: usize) -> T {
+        self.palette[self.get_index(PalettedStorage::<T>::index(x, y, z))]
+    }
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: T) {
+        let index = PalettedStorage::<T>::index(x, y, z);
+        let old = self.get_index(index);
+        if self.palette[old] == value {
+            return;
+        }
+        //released first so a repack can already drop the entry being overwritten
+        self.counts[old] -= 1;
+        let palette_index = match self.palette.iter().position(|entry| *entry == value) {
+            Some(palette_index) => palette_index,
+            None => {
+                if self.palette.len() == 1 << self.bits {
+                    self.repack();
+                }
+                self.palette.push(value);
+                self.counts.push(0);
+                if self.palette.len() > 1 << self.bits {
+                    self.resize(self.bits + 1);
+                }
+                self.palette.len() - 1
+            }
+        };
+        self.counts[palette_index] += 1;
+        //the overwrite left a single value, go back to storing no indices
+        if self.counts[palette_index] == 4096 {
+            *self = PalettedStorage::uniform(value);
+            return;
+        }
+        self.set_index(index, palette_index);
+    }
+    //drops palette entries nothing points to anymore, called before the palette would grow
+    fn repack(&mut self) {
+        if self.counts.iter().all(|count| *count > 0) {
+            return;
+        }
+        let indices: Vec<usize> = (0..4096).map(|index| self.get_index(index)).collect();
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        let mut counts = Vec::new();
+        for (old, entry) in self.palette.iter().enumerate() {
+            if self.counts[old] > 0 {
+                remap[old] = palette.len();
+                palette.push(*entry);
+                counts.push(self.counts[old]);
+            }
+        }
+        self.palette = palette;
+        self.counts = counts;
+        let bits = match self.palette.len() {
+            1 => 0,
+            len => usize::BITS - (len - 1).leading_zeros(),
+        } as u8;
+        self.bits = bits;
+        self.data = if bits == 0 {
+            Vec::new()
+        } else {
+            vec![0; PalettedStorage::<T>::words(bits)]
+        };
+        if bits > 0 {
+            for (index, old) in indices.iter().enumerate() {
+                self.set_index(index, remap[*old]);
+            }
+        }
+    }
+    fn resize(&mut self, bits: u8) {
+        let indices: Vec<usize> = (0..4096).map(|index| self.get_index(index)).collect();
+        self.bits = bits;
+        self.data = vec![0; PalettedStorage::<T>::words(bits)];
+        for (index, palette_index) in indices.into_iter().enumerate() {
+            self.set_index(index, palette_index);
+        }
+    }
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.palette.capacity() * std::mem::size_of::<T>()
+            + self.counts.capacity() * std::mem::size_of::<u16>()
+            + self.data.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(index: usize) -> (usize, usize, usize) {
+        (index >> 8, (index >> 4) & 15, index & 15)
+    }
+
+    #[test]
+    fn get_returns_what_was_set() {
+        let mut storage = PalettedStorage::uniform(0u32);
+        let value = |index: usize| (index * 7 % 13) as u32;
+        for index in 0..4096 {
+            let (x, y, z) = position(index);
+            storage.set(x, y, z, value(index));
+        }
+        for index in 0..4096 {
+            let (x, y, z) = position(index);
+            assert_eq!(storage.get(x, y, z), value(index));
+        }
+        let array = storage.to_array();
+        assert_eq!(PalettedStorage::from_array(&array).to_array(), array);
+    }
+
+    #[test]
+    fn grows_past_each_bit_width() {
+        let mut storage = PalettedStorage::uniform(0u32);
+        assert_eq!(storage.bits, 0);
+        for value in 1..40u32 {
+            let (x, y, z) = position(value as usize * 97);
+            storage.set(x, y, z, value);
+            let distinct = value as usize + 1;
+            let bits = usize::BITS - (distinct - 1).leading_zeros();
+            assert_eq!(storage.bits as u32, bits, "{} distinct values", distinct);
+            assert_eq!(
+                storage.data.len(),
+                PalettedStorage::<u32>::words(storage.bits)
+            );
+        }
+        for value in 0..40u32 {
+            let (x, y, z) = position(value as usize * 97);
+            assert_eq!(storage.get(x, y, z), value);
+        }
+    }
+
+    #[test]
+    fn overwriting_to_one_value_goes_back_to_uniform() {
+        let mut storage = PalettedStorage::uniform(0u32);
+        for value in 1..5 {
+            storage.set(value as usize, 0, 0, value);
+        }
+        assert_eq!(storage.bits, 3);
+        for x in 0..5 {
+            storage.set(x, 0, 0, 9);
+        }
+        //0 and 9 are still live
+        assert_eq!(storage.counts.iter().filter(|count| **count > 0).count(), 2);
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    storage.set(x, y, z, 9);
+                }
+            }
+        }
+        assert_eq!(storage.palette, vec![9]);
+        assert_eq!(storage.bits, 0);
+        assert!(storage.data.is_empty());
+        assert_eq!(storage.get(3, 4, 5), 9);
+    }
+
+    #[test]
+    fn repack_drops_unused_entries_before_growing() {
+        let mut storage = PalettedStorage::uniform(0u32);
+        storage.set(0, 0, 0, 1);
+        assert_eq!(storage.bits, 1);
+        //1 is no longer used, so 2 takes its place instead of widening to 2 bits
+        storage.set(0, 0, 0, 2);
+        assert_eq!(storage.bits, 1);
+        assert_eq!(storage.palette, vec![0, 2]);
+        assert_eq!(storage.counts, vec![4095, 1]);
+        storage.set(1, 0, 0, 3);
+        assert_eq!(storage.bits, 2);
+        assert_eq!(storage.get(0, 0, 0), 2);
+        assert_eq!(storage.get(1, 0, 0), 3);
+        assert_eq!(storage.get(2, 0, 0), 0);
+    }
+
+    #[test]
+    fn memory_usage_follows_bit_width() {
+        let uniform = PalettedStorage::uniform(0u32);
+        let mut storage = uniform.clone();
+        storage.set(0, 0, 0, 1);
+        let one_bit = storage.memory_usage();
+        assert!(one_bit >= uniform.memory_usage() + 4096 / 8);
+        for value in 2..16 {
+            storage.set(value as usize, 0, 0, value);
+        }
+        assert!(storage.memory_usage() >= uniform.memory_usage() + 4096 * 4 / 8);
+        assert!(storage.memory_usage() > one_bit);
+        for value in 1..16 {
+            storage.set(value as usize, 0, 0, 0);
+        }
+        storage.set(0, 0, 0, 0);
+        assert_eq!(storage.memory_usage(), uniform.memory_usage());
+    }
+}
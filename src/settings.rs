@@ -0,0 +1,80 @@
+use json::JsonValue;
+
+use crate::{
+    lang::DEFAULT_LOCALE,
+    lod::{LodSettings, MAX_LOD},
+    meshing::{MeshSettings, MissingNeighbors},
+};
+
+pub struct Settings {
+    pub atlas_border_padding: u32,
+    pub atlas_texture_padding: u32,
+    pub atlas_texture_extrusion: u32,
+    pub mipmap_levels: u32,
+    pub anisotropic_filtering: f32,
+    pub atlas_cache: bool,
+    pub dump_atlas: bool,
+    pub locale: String,
+    //refuse to start when any texture or model is missing
+    pub strict_assets: bool,
+    //per vertex light and ambient occlusion in chunk meshes
+    pub smooth_lighting: bool,
+    //"air" or "solid", how chunk edges are meshed next to chunks that aren't loaded
+    pub missing_neighbors: MissingNeighbors,
+    //"lod_distances" in blocks for each coarser level and "lod_hysteresis"
+    pub lod: LodSettings,
+}
+impl Settings {
+    pub fn load() -> Settings {
+        let json = std::fs::read_to_string("settings.json")
+            .ok()
+            .and_then(|data| json::parse(data.as_str()).ok())
+            .unwrap_or(JsonValue::Null);
+        Settings::from_json(&json)
+    }
+    pub fn from_json(json: &JsonValue) -> Settings {
+        Settings {
+            atlas_border_padding: json["atlas_border_padding"].as_u32().unwrap_or(0),
+            atlas_texture_padding: json["atlas_texture_padding"].as_u32().unwrap_or(2),
+            atlas_texture_extrusion: json["atlas_texture_extrusion"].as_u32().unwrap_or(4),
+            mipmap_levels: json["mipmap_levels"].as_u32().unwrap_or(4),
+            anisotropic_filtering: json["anisotropic_filtering"].as_f32().unwrap_or(16.),
+            atlas_cache: json["atlas_cache"].as_bool().unwrap_or(true),
+            dump_atlas: json["dump_atlas"].as_bool().unwrap_or(false),
+            locale: json["locale"]
+                .as_str()
+                .unwrap_or(DEFAULT_LOCALE)
+                .to_lowercase(),
+            strict_assets: json["strict_assets"].as_bool().unwrap_or(false),
+            smooth_lighting: json["smooth_lighting"].as_bool().unwrap_or(true),
+            missing_neighbors: json["missing_neighbors"]
+                .as_str()
+                .and_then(MissingNeighbors::from_name)
+                .unwrap_or(MissingNeighbors::Air),
+            lod: {
+                let default = LodSettings::default();
+                let mut distances = default.distances;
+                for (level, distance) in json["lod_distances"]
+                    .members()
+                    .take(MAX_LOD as usize)
+                    .enumerate()
+                {
+                    distances[level] = distance.as_f32().unwrap_or(distances[level]);
+                }
+                LodSettings::new(
+                    distances,
+                    json["lod_hysteresis"]
+                        .as_f32()
+                        .unwrap_or(default.hysteresis),
+                )
+            },
+        }
+    }
+    pub fn mesh_settings(&self) -> MeshSettings {
+        MeshSettings {
+            smooth_lighting: self.smooth_lighting,
+            missing_neighbors: self.missing_neighbors,
+            lod: self.lod,
+        }
+    }
+}
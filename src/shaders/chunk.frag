@@ -1,14 +1,37 @@
 #version 330 core
 uniform sampler2D frag_texture;
+uniform float time;
+uniform float sky_brightness;
 in vec2 frag_tex;
-flat in int frag_light;
+flat in vec2 frag_frame_offset;
+flat in vec4 frag_tile;
+flat in float frag_blend;
+in vec4 frag_light;
+in float frag_ao;
+in vec3 frag_tint;
+flat in int frag_effect;
 out vec4 final_color;
+
+#define PI 3.14159265359
+
+//merged faces repeat their texture inside frag_tile, the gradients of the unwrapped
+//coordinates keep mip selection from jumping at the tile borders
+vec4 sample_atlas(float frame_offset) {
+  if(frag_tile.z == 0.)
+    return texture(frag_texture, frag_tex + vec2(0., frame_offset));
+  vec2 unwrapped = frag_tile.xy + frag_tex*frag_tile.zw;
+  vec2 wrapped = frag_tile.xy + fract(frag_tex)*frag_tile.zw;
+  return textureGrad(frag_texture, wrapped + vec2(0., frame_offset), dFdx(unwrapped), dFdy(unwrapped));
+}
+//effects
 void main() {
-  final_color = texture(frag_texture, frag_tex);
+  final_color = mix(sample_atlas(frag_frame_offset.x), sample_atlas(frag_frame_offset.y), frag_blend);
   if(final_color[3] == 0)
     discard;
+  final_color.xyz *= frag_tint;
+  final_color = apply_fragment_effect(frag_effect, final_color);
   
-  final_color.x *= max(float(frag_light&15), (frag_light>>12)&15)/16.0f;
-  final_color.y *= max(float((frag_light>>4)&15), ((frag_light>>12)&15))/16.0f;
-  final_color.z *= max(float((frag_light>>8)&15), ((frag_light>>12)&15))/16.0f;
+  float sky = frag_light.w*sky_brightness;
+  final_color.xyz *= max(frag_light.xyz, vec3(sky))/16.0f;
+  final_color.xyz *= mix(0.45, 1., frag_ao/3.);
 }
\ No newline at end of file
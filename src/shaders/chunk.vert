@@ -2,30 +2,48 @@
 uniform mat4 model;
 uniform mat4 projection_view;
 uniform float time;
+//frames, frame time, interpolate, frame height
+uniform vec4 texture_animations[64];
 
 layout (location = 0) in vec3 pos;
 layout (location = 1) in vec2 tex;
 layout (location = 2) in int render_data;
 layout (location = 3) in int light;
+layout (location = 4) in int animation;
+layout (location = 5) in vec3 tint;
+layout (location = 6) in vec4 tile;
+layout (location = 7) in float ao;
 
 #define PI 3.14159265359
 
 out vec2 frag_tex;
-flat out int frag_light;
+//v offset of the current and the next animation frame
+flat out vec2 frag_frame_offset;
+flat out vec4 frag_tile;
+flat out float frag_blend;
+//r, g, b and sky, interpolated for smooth lighting
+out vec4 frag_light;
+out float frag_ao;
+out vec3 frag_tint;
+flat out int frag_effect;
+//effects
 void main() {
-  vec3 position = pos;
-  if(render_data == 1)
-    position.y += sin(time+(position.x/16*2*PI)+(position.z/16*2*PI*3))*0.1 - 0.1;
-  if(render_data == 2){
-    position.z += cos(time+ceil(position.x)+50)*0.2*mod(position.y,1.);        
-    position.x += sin(time+ceil(position.x))*0.2*mod(position.y,1.);
-  }
-  if(render_data == 4){
-    position.z += cos(time*2+position.y)*0.03;        
-    position.x += sin(time*2+position.y+1)*0.03;
-    position.y += cos(time*2+position.y+2)*0.03;    
-  }
+  vec3 position = apply_vertex_effect(render_data, pos);
   gl_Position = projection_view * model * vec4(position, 1.0);
   frag_tex = tex;
-  frag_light = light;
+  frag_frame_offset = vec2(0.);
+  frag_blend = 0.;
+  if(animation > 0){
+    vec4 texture_animation = texture_animations[animation-1];
+    float progress = time/texture_animation.y;
+    float frame = mod(floor(progress), texture_animation.x);
+    frag_frame_offset = vec2(frame, mod(frame+1., texture_animation.x))*texture_animation.w;
+    if(texture_animation.z > 0.5)
+      frag_blend = fract(progress);
+  }
+  frag_light = vec4(light&15, (light>>4)&15, (light>>8)&15, (light>>12)&15);
+  frag_ao = ao;
+  frag_tint = tint;
+  frag_effect = render_data;
+  frag_tile = tile;
 }
\ No newline at end of file
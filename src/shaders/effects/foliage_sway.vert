@@ -0,0 +1,3 @@
+position.z += cos(time*speed+ceil(position.x)+50)*amplitude*mod(position.y,1.);
+position.x += sin(time*speed+ceil(position.x))*amplitude*mod(position.y,1.);
+return position;
@@ -0,0 +1,4 @@
+position.z += cos(time*speed+position.y)*amplitude;
+position.x += sin(time*speed+position.y+1)*amplitude;
+position.y += cos(time*speed+position.y+2)*amplitude;
+return position;
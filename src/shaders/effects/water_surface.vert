@@ -0,0 +1,2 @@
+position.y += sin(time*speed+(position.x/16*2*PI)+(position.z/16*2*PI*3))*amplitude - amplitude;
+return position;
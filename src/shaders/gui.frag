@@ -1,10 +1,14 @@
 #version 330 core
 uniform sampler2D frag_texture;
+uniform sampler2D glyph_texture;
 in vec2 frag_tex;
 in vec4 frag_col;
+flat in float frag_glyph;
 out vec4 final_color;
 void main() {
-  if(frag_tex.x == 0 && frag_tex.y == 0){
+  if(frag_glyph > 0.5){
+    final_color = vec4(1, 1, 1, texture(glyph_texture, frag_tex).r)*frag_col;
+  } else if(frag_tex.x == 0 && frag_tex.y == 0){
     final_color = frag_col;
   } else {
     final_color = texture(frag_texture, frag_tex)*frag_col;
@@ -3,10 +3,13 @@
 layout (location = 0) in vec2 pos;
 layout (location = 1) in vec2 tex;
 layout (location = 2) in vec4 col;
+layout (location = 3) in float glyph;
 out vec2 frag_tex;
 out vec4 frag_col;
+flat out float frag_glyph;
 void main() {
   gl_Position = vec4(pos, 0, 1);
   frag_tex = tex;
   frag_col = col;
+  frag_glyph = glyph;
 }
\ No newline at end of file
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use image::RgbaImage;
+
+use crate::game::AtlassedTexture;
+use crate::util::Face;
+
+pub const NO_TINT: [u8; 3] = [255, 255, 255];
+
+//one entry per x/z column of a chunk, sent by the server
+#[derive(Clone, Copy, Debug)]
+pub struct ColumnBiome {
+    pub temperature: u8,
+    pub humidity: u8,
+    pub color: [u8; 3],
+}
+impl Default for ColumnBiome {
+    fn default() -> Self {
+        ColumnBiome {
+            temperature: 128,
+            humidity: 128,
+            color: NO_TINT,
+        }
+    }
+}
+
+pub type ChunkBiomes = [[ColumnBiome; 16]; 16];
+
+//copy of a colormap texture, kept on the cpu because tints are baked while meshing
+pub struct Colormap {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+impl Colormap {
+    pub fn from_atlas(atlas: &RgbaImage, texture: &AtlassedTexture) -> Self {
+        let (width, height) = (texture.w.max(1), texture.h.max(1));
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = atlas.get_pixel(
+                    (texture.x + x).min(atlas.width() - 1),
+                    (texture.y + y).min(atlas.height() - 1),
+                );
+                pixels.push([pixel[0], pixel[1], pixel[2]]);
+            }
+        }
+        Colormap {
+            width,
+            height,
+            pixels,
+        }
+    }
+    //same triangle layout as the usual grass/foliage colormaps, hot on the left and wet at the top
+    pub fn sample(&self, biome: &ColumnBiome) -> [u8; 3] {
+        let temperature = biome.temperature as f32 / 255.;
+        let humidity = biome.humidity as f32 / 255. * temperature;
+        let x = ((1. - temperature) * (self.width - 1) as f32).round() as u32;
+        let y = ((1. - humidity) * (self.height - 1) as f32).round() as u32;
+        self.pixels[(x + y * self.width) as usize]
+    }
+}
+
+#[derive(Clone)]
+pub enum TintSource {
+    None,
+    Fixed([u8; 3]),
+    Colormap(Arc<Colormap>),
+    Server,
+}
+
+#[derive(Clone)]
+pub struct BlockTint {
+    pub source: TintSource,
+    //bitmask indexed by face id, only used by cubes so grass sides can stay untinted
+    pub faces: u8,
+}
+impl BlockTint {
+    pub fn none() -> Self {
+        BlockTint {
+            source: TintSource::None,
+            faces: 0,
+        }
+    }
+    pub fn color(&self, biome: &ColumnBiome) -> [u8; 3] {
+        match &self.source {
+            TintSource::None => NO_TINT,
+            TintSource::Fixed(color) => *color,
+            TintSource::Colormap(colormap) => colormap.sample(biome),
+            TintSource::Server => biome.color,
+        }
+    }
+    pub fn color_for_face(&self, face: &Face, biome: &ColumnBiome) -> [u8; 3] {
+        if self.faces & (1 << *face as u8) != 0 {
+            self.color(biome)
+        } else {
+            NO_TINT
+        }
+    }
+}
@@ -8,6 +8,8 @@ use json::JsonValue;
 use ultraviolet::*;
 
 use crate::game::AtlassedTexture;
+use crate::model::Model;
+use crate::tint::ColumnBiome;
 
 pub struct BlockRenderData {
     pub json: JsonValue,
@@ -36,9 +38,87 @@ pub struct ItemTextureSideFace {
     pub u: f32,
     pub v: f32,
 }
+//where an item is drawn, each context has its own transform for model items
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemContext {
+    Gui,
+    Held,
+    Ground,
+    FirstPerson,
+}
+#[derive(Clone, Copy, Debug)]
+pub struct ItemTransform {
+    pub translation: Vec3,
+    //radians, content.json uses degrees
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+impl ItemTransform {
+    pub fn from_json(json: &JsonValue, default: ItemTransform) -> ItemTransform {
+        let vec3 = |json: &JsonValue| {
+            if json.is_null() {
+                None
+            } else {
+                Some(Vec3::new(
+                    json[0].as_f32().unwrap(),
+                    json[1].as_f32().unwrap(),
+                    json[2].as_f32().unwrap(),
+                ))
+            }
+        };
+        ItemTransform {
+            translation: vec3(&json["translation"]).unwrap_or(default.translation),
+            rotation: vec3(&json["rotation"])
+                .map(|rotation| rotation * (std::f32::consts::PI / 180.))
+                .unwrap_or(default.rotation),
+            scale: vec3(&json["scale"]).unwrap_or(default.scale),
+        }
+    }
+    //lower right of the screen, relative to the camera
+    pub fn first_person() -> ItemTransform {
+        ItemTransform::new(
+            Vec3::new(0.3, -0.6, -0.9),
+            Vec3::new(0., -20., 0.),
+            Vec3::new(1., 1., 1.),
+        )
+    }
+    pub fn new(translation: Vec3, rotation_deg: Vec3, scale: Vec3) -> ItemTransform {
+        ItemTransform {
+            translation,
+            rotation: rotation_deg * (std::f32::consts::PI / 180.),
+            scale,
+        }
+    }
+    //rotates and scales around the middle of the block the model occupies
+    pub fn matrix(&self) -> Mat4 {
+        let center = Vec3::new(0., 0.5, 0.);
+        Mat4::from_translation(self.translation + center)
+            * Mat4::from_euler_angles(self.rotation.z, self.rotation.x, self.rotation.y)
+            * Mat4::from_nonuniform_scale(self.scale)
+            * Mat4::from_translation(-center)
+    }
+}
+pub struct ItemModelData {
+    pub model: Model,
+    pub gui: ItemTransform,
+    pub held: ItemTransform,
+    pub ground: ItemTransform,
+    pub first_person: ItemTransform,
+}
+impl ItemModelData {
+    pub fn transform(&self, context: ItemContext) -> &ItemTransform {
+        match context {
+            ItemContext::Gui => &self.gui,
+            ItemContext::Held => &self.held,
+            ItemContext::Ground => &self.ground,
+            ItemContext::FirstPerson => &self.first_person,
+        }
+    }
+}
 pub enum ItemModel {
     Texture(ItemTexture),
     Block(u32),
+    Model(ItemModelData),
 }
 impl ItemModel {
     pub fn build_texture(texture: &AtlassedTexture, image: &RgbaImage) -> ItemModel {
@@ -141,6 +221,7 @@ pub enum NetworkMessageS2C {
     PlayerAbilities(f32, MovementType) = 17,
     TeleportPlayer(f32, f32, f32, f32) = 18,
     BlockAnimation(i32, i32, i32, u32) = 19,
+    ChunkBiomes(i32, i32, Vec<ColumnBiome>) = 20,
 }
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum MovementType {
@@ -277,6 +358,26 @@ impl NetworkMessageS2C {
                 data.read_be().unwrap(),
                 data.read_be().unwrap(),
             )),
+            20 => Some(Self::ChunkBiomes(
+                data.read_be().unwrap(),
+                data.read_be().unwrap(),
+                {
+                    //16x16 columns, x major like the blocks in LoadChunk
+                    let mut biomes = Vec::with_capacity(16 * 16);
+                    for _ in 0..16 * 16 {
+                        biomes.push(ColumnBiome {
+                            temperature: data.read_be().unwrap(),
+                            humidity: data.read_be().unwrap(),
+                            color: [
+                                data.read_be().unwrap(),
+                                data.read_be().unwrap(),
+                                data.read_be().unwrap(),
+                            ],
+                        });
+                    }
+                    biomes
+                },
+            )),
             _ => None,
         }
     }
@@ -689,6 +790,75 @@ impl Face {
     }
 }
 
+//quarter turns of a block variant, first about the x axis and then about the y axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BlockRotation {
+    pub x: u8,
+    pub y: u8,
+    pub uv_lock: bool,
+}
+impl BlockRotation {
+    pub fn from_degrees(x: i32, y: i32, uv_lock: bool) -> Self {
+        BlockRotation {
+            x: (x / 90).rem_euclid(4) as u8,
+            y: (y / 90).rem_euclid(4) as u8,
+            uv_lock,
+        }
+    }
+    pub fn is_identity(&self) -> bool {
+        self.x == 0 && self.y == 0
+    }
+    fn turn_x(face: Face) -> Face {
+        match face {
+            Face::Up => Face::Front,
+            Face::Front => Face::Down,
+            Face::Down => Face::Back,
+            Face::Back => Face::Up,
+            face => face,
+        }
+    }
+    fn turn_y(face: Face) -> Face {
+        match face {
+            Face::Front => Face::Right,
+            Face::Right => Face::Back,
+            Face::Back => Face::Left,
+            Face::Left => Face::Front,
+            face => face,
+        }
+    }
+    //where a face of the unrotated model ends up in the world
+    pub fn rotate_face(&self, mut face: Face) -> Face {
+        for _ in 0..self.x {
+            face = Self::turn_x(face);
+        }
+        for _ in 0..self.y {
+            face = Self::turn_y(face);
+        }
+        face
+    }
+    //which face of the unrotated model ends up facing the given world direction
+    pub fn unrotate_face(&self, mut face: Face) -> Face {
+        for _ in 0..(4 - self.y) % 4 {
+            face = Self::turn_y(face);
+        }
+        for _ in 0..(4 - self.x) % 4 {
+            face = Self::turn_x(face);
+        }
+        face
+    }
+    //rotates a point inside the unit block about its center
+    pub fn rotate_point(&self, point: Vec3) -> Vec3 {
+        let mut centered = point - Vec3::new(0.5, 0.5, 0.5);
+        for _ in 0..self.x {
+            centered = Vec3::new(centered.x, centered.z, -centered.y);
+        }
+        for _ in 0..self.y {
+            centered = Vec3::new(-centered.z, centered.y, centered.x);
+        }
+        centered + Vec3::new(0.5, 0.5, 0.5)
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Position {
     pub x: f32,
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashSet;
+
+use crate::util::*;
+
+//which pairs of chunk faces are connected through non opaque blocks, bit a*6+b
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FaceConnections(u64);
+impl FaceConnections {
+    pub fn none() -> Self {
+        FaceConnections(0)
+    }
+    //used until a chunk has a mesh, so unmeshed chunks never hide what is behind them
+    pub fn all() -> Self {
+        FaceConnections((1 << 36) - 1)
+    }
+    pub fn connected(&self, a: Face, b: Face) -> bool {
+        self.0 & (1 << (a as u64 * 6 + b as u64)) != 0
+    }
+    fn connect(&mut self, a: Face, b: Face) {
+        self.0 |= 1 << (a as u64 * 6 + b as u64);
+        self.0 |= 1 << (b as u64 * 6 + a as u64);
+    }
+    //flood fills every pocket of non opaque blocks and connects all faces the pocket touches
+    pub fn compute(blocks: &[[[u32; 16]; 16]; 16], is_opaque: impl Fn(u32) -> bool) -> Self {
+        let mut connections = FaceConnections::none();
+        let mut visited = [[[false; 16]; 16]; 16];
+        let mut stack = Vec::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    if visited[x][y][z] || is_opaque(blocks[x][y][z]) {
+                        continue;
+                    }
+                    visited[x][y][z] = true;
+                    stack.push((x, y, z));
+                    let mut touched = 0u8;
+                    while let Some((x, y, z)) = stack.pop() {
+                        touched |= FaceConnections::border_faces(x, y, z);
+                        for face in Face::all() {
+                            let offset = face.get_offset();
+                            let nx = x as i32 + offset.x;
+                            let ny = y as i32 + offset.y;
+                            let nz = z as i32 + offset.z;
+                            if !(0..16).contains(&nx)
+                                || !(0..16).contains(&ny)
+                                || !(0..16).contains(&nz)
+                            {
+                                continue;
+                            }
+                            let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+                            if visited[nx][ny][nz] || is_opaque(blocks[nx][ny][nz]) {
+                                continue;
+                            }
+                            visited[nx][ny][nz] = true;
+                            stack.push((nx, ny, nz));
+                        }
+                    }
+                    for a in Face::all() {
+                        for b in Face::all() {
+                            if touched & (1 << *a as u8) != 0 && touched & (1 << *b as u8) != 0 {
+                                connections.connect(*a, *b);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        connections
+    }
+    fn border_faces(x: usize, y: usize, z: usize) -> u8 {
+        let mut faces = 0;
+        if z == 0 {
+            faces |= 1 << Face::Front as u8;
+        }
+        if z == 15 {
+            faces |= 1 << Face::Back as u8;
+        }
+        if y == 15 {
+            faces |= 1 << Face::Up as u8;
+        }
+        if y == 0 {
+            faces |= 1 << Face::Down as u8;
+        }
+        if x == 0 {
+            faces |= 1 << Face::Left as u8;
+        }
+        if x == 15 {
+            faces |= 1 << Face::Right as u8;
+        }
+        faces
+    }
+}
+//breadth first walk from the camera chunk, a chunk is only entered through a face its
+//predecessor connects to the face it was entered from, and never against the walk direction
+pub fn visible_chunks(
+    start: ChunkPosition,
+    connections: impl Fn(ChunkPosition) -> Option<FaceConnections>,
+    in_frustum: impl Fn(ChunkPosition) -> bool,
+) -> FxHashSet<ChunkPosition> {
+    let mut visible = FxHashSet::default();
+    let mut queue = VecDeque::new();
+    visible.insert(start);
+    queue.push_back((start, None::<Face>, 0u8));
+    while let Some((position, entered_from, directions)) = queue.pop_front() {
+        let Some(chunk_connections) = connections(position) else {
+            continue;
+        };
+        for face in Face::all() {
+            if directions & (1 << face.opposite() as u8) != 0 {
+                continue;
+            }
+            if let Some(entered_from) = entered_from {
+                if !chunk_connections.connected(entered_from, *face) {
+                    continue;
+                }
+            }
+            let next = position.with_offset(face);
+            if visible.contains(&next) || !in_frustum(next) {
+                continue;
+            }
+            visible.insert(next);
+            queue.push_back((next, Some(face.opposite()), directions | (1 << *face as u8)));
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIR: u32 = 0;
+    const STONE: u32 = 1;
+
+    fn compute(block: impl Fn(usize, usize, usize) -> u32) -> FaceConnections {
+        let mut blocks = [[[AIR; 16]; 16]; 16];
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    blocks[x][y][z] = block(x, y, z);
+                }
+            }
+        }
+        FaceConnections::compute(&blocks, |block| block == STONE)
+    }
+    fn connected_pairs(connections: FaceConnections) -> Vec<(Face, Face)> {
+        let mut pairs = Vec::new();
+        for a in Face::all() {
+            for b in Face::all() {
+                if a != b && connections.connected(*a, *b) {
+                    pairs.push((*a, *b));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn air_connects_every_pair() {
+        assert_eq!(compute(|_, _, _| AIR), FaceConnections::all());
+    }
+
+    #[test]
+    fn stone_connects_nothing() {
+        assert_eq!(compute(|_, _, _| STONE), FaceConnections::none());
+    }
+
+    #[test]
+    fn tunnel_connects_only_its_ends() {
+        let connections = compute(|_, y, z| if y == 8 && z == 8 { AIR } else { STONE });
+        assert_eq!(
+            connected_pairs(connections),
+            vec![(Face::Left, Face::Right), (Face::Right, Face::Left)]
+        );
+    }
+
+    #[test]
+    fn wall_separates_both_halves() {
+        let connections = compute(|x, _, _| if x == 8 { STONE } else { AIR });
+        assert!(!connections.connected(Face::Left, Face::Right));
+        for face in [Face::Front, Face::Back, Face::Up, Face::Down] {
+            assert!(connections.connected(Face::Left, face));
+            assert!(connections.connected(Face::Right, face));
+        }
+        assert!(connections.connected(Face::Up, Face::Down));
+    }
+
+    #[test]
+    fn shell_hides_chunks_behind_it() {
+        let inside = |position: ChunkPosition| {
+            [position.x, position.y, position.z]
+                .iter()
+                .all(|value| (-2..=2).contains(value))
+        };
+        let walk = |shell: bool| {
+            visible_chunks(
+                ChunkPosition { x: 0, y: 0, z: 0 },
+                |position| {
+                    let distance = position.x.abs().max(position.y.abs()).max(position.z.abs());
+                    inside(position).then(|| {
+                        if shell && distance == 1 {
+                            FaceConnections::none()
+                        } else {
+                            FaceConnections::all()
+                        }
+                    })
+                },
+                inside,
+            )
+        };
+        assert_eq!(walk(false).len(), 5 * 5 * 5);
+        let visible = walk(true);
+        //the camera chunk and the six shell chunks next to it
+        assert_eq!(visible.len(), 7);
+        assert!(!visible.contains(&ChunkPosition { x: 2, y: 0, z: 0 }));
+        assert!(!visible.contains(&ChunkPosition { x: 1, y: 1, z: 0 }));
+    }
+}